@@ -3,25 +3,470 @@ use egui::TextureHandle;
 use egui_file::FileDialog;
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
 pub use crate::peppi::*;
-use crate::ui::helpers::{format_date, format_duration};
-
-#[derive(PartialEq, serde::Deserialize, serde::Serialize)]
+use crate::ui::charts::{percent_line_chart, win_loss_chart};
+use crate::ui::helpers::{
+    format_date, format_duration, format_playtime, format_timestamp, port_color, TimestampPrecision,
+};
+use crate::ui::stat_card::{stat_card, StatCard};
+use crate::watch::ReplayWatcher;
+
+#[derive(Default, PartialEq, serde::Deserialize, serde::Serialize)]
 pub(crate) enum DemoType {
     Manual,
+    #[default]
     ReplayData,
     ManyHomogeneous,
     ManyHeterogenous,
 }
 
-/// We derive Deserialize/Serialize so we can persist app state on shutdown.
+/// Deserializer for [`LenientState`] fields: a value that fails to decode becomes `None`
+/// instead of failing the whole struct, so one corrupt field can't wipe the connect code and
+/// replay directory. Buffering the value (as `untagged` does) loses enum variants in RON, so
+/// this is only used once a strict restore has failed.
+fn lenient_field<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Lenient<T> {
+        Valid(T),
+        Invalid(serde::de::IgnoredAny),
+    }
+
+    Ok(
+        match <Lenient<T> as serde::Deserialize>::deserialize(deserializer)? {
+            Lenient::Valid(value) => Some(value),
+            Lenient::Invalid(_) => {
+                log::warn!(
+                    "Discarding corrupt persisted value for {}",
+                    std::any::type_name::<T>()
+                );
+                None
+            }
+        },
+    )
+}
+
+/// Declares [`LenientState`] with an `Option` of each persisted [`Eppi`] field, and how to
+/// copy the ones that decoded onto an `Eppi`.
+macro_rules! lenient_state {
+    ($($field:ident: $ty:ty,)*) => {
+        /// Persisted [`Eppi`] fields, each decoded on its own. Used by
+        /// [`Eppi::restore_leniently`] when the stored state as a whole fails to decode.
+        /// Fields missing or corrupt are `None` and keep their [`Eppi::default`] value.
+        #[derive(Default, serde::Deserialize)]
+        #[serde(default)]
+        struct LenientState {
+            $(
+                #[serde(deserialize_with = "lenient_field")]
+                $field: Option<$ty>,
+            )*
+        }
+
+        impl LenientState {
+            /// The field names, which must cover what [`Eppi`] persists.
+            #[cfg(test)]
+            const FIELDS: &'static [&'static str] = &[$(stringify!($field),)*];
+
+            fn apply(self, app: &mut Eppi) {
+                $(
+                    if let Some(value) = self.$field {
+                        app.$field = value;
+                    }
+                )*
+            }
+        }
+    };
+}
+
+// Every persisted (not `#[serde(skip)]`) field of `Eppi`
+lenient_state! {
+    connect_code: String,
+    name_tag: String,
+    replay_dir: String,
+    replay_dirs: Vec<String>,
+    demo: DemoType,
+    striped: bool,
+    overline: bool,
+    resizable: bool,
+    clickable: bool,
+    num_rows: usize,
+    scroll_to_row_slider: usize,
+    scroll_to_row: Option<usize>,
+    selection: std::collections::HashSet<String>,
+    checked: bool,
+    reversed: bool,
+    count_draws_in_win_rate: bool,
+    date_source: DateSource,
+    rank_cache_ttl_hours: u64,
+    sort_column: SortColumn,
+    sort_ascending: bool,
+    default_sort_descending: bool,
+    group_by_session: bool,
+    absolute_dates: bool,
+    hide_short_games: bool,
+    min_game_seconds: u32,
+    page_size: PageSize,
+    scan_options: ScanOptions,
+    watch_replay_dir: bool,
+    playback_path: String,
+    web_config: WebConfig,
+    offline_mode: bool,
+}
+
+/// Which aggregated breakdown an export dialog will write.
+#[derive(Clone, Copy, PartialEq)]
+enum StatsExport {
+    Character,
+    Stage,
+    Opponent,
+    /// Every loaded replay, not just stats.
+    Replays,
+    /// The replays selected in the table, in the same format as `Replays`.
+    Selected,
+    /// A PNG summary card; see [`StatCardCapture`].
+    StatCard,
+}
+
+impl StatsExport {
+    fn default_filename(self) -> &'static str {
+        match self {
+            StatsExport::Character => "character_stats.csv",
+            StatsExport::Stage => "stage_stats.csv",
+            StatsExport::Opponent => "opponent_stats.csv",
+            StatsExport::Replays => "replays.json",
+            StatsExport::Selected => "selected_replays.json",
+            StatsExport::StatCard => "stat_card.png",
+        }
+    }
+}
+
+/// A stat card being saved. egui can't render offscreen, so the card is shown for a couple of
+/// frames and cut out of a screenshot of the window.
+struct StatCardCapture {
+    path: PathBuf,
+    /// Where the card was drawn last frame, in points.
+    rect: Option<egui::Rect>,
+    screenshot_requested: bool,
+    started: Instant,
+}
+
+/// Replays table column the rows are sorted by.
+#[derive(Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+enum SortColumn {
+    Player1,
+    Player2,
+    Result,
+    Stage,
+    #[default]
+    Date,
+    Duration,
+    OpponentRank,
+}
+
+/// [`rank_ordinal`] of the replay's opponent, if their rank has been looked up and is a tier.
+fn opponent_rank_ordinal(replay: &ReplayInfo) -> Option<usize> {
+    replay
+        .opponent_rank
+        .as_ref()
+        .and_then(|rank| rank_ordinal(&rank.rank))
+}
+
+/// Hover text for a replays table row: full path, replay format and exact start time.
+fn replay_tooltip(replay: &ReplayInfo) -> String {
+    let path = if replay.file_path.is_empty() {
+        "No file"
+    } else {
+        &replay.file_path
+    };
+    let version = replay.format_version.as_deref().unwrap_or("Unknown");
+    let started = replay.start_time.map_or_else(
+        || "Unknown".to_string(),
+        |start| format_timestamp(start, TimestampPrecision::Seconds),
+    );
+    format!("{path}\nSlippi format: {version}\nStarted: {started}")
+}
+
+impl SortColumn {
+    /// Ascending order of two replays by this column. `me` puts your wins before your losses
+    /// when sorting by result.
+    fn compare(self, a: &ReplayInfo, b: &ReplayInfo, me: PlayerTag<'_>) -> std::cmp::Ordering {
+        fn side_names(replay: &ReplayInfo, side: usize) -> String {
+            let names: Vec<_> = replay.sides()[side]
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect();
+            names.join(" & ").to_lowercase()
+        }
+        fn result_rank(replay: &ReplayInfo, me: PlayerTag<'_>) -> u8 {
+            match (replay.won_by(me), &replay.result) {
+                (Some(true), _) => 0,
+                (Some(false), _) => 1,
+                (None, GameResult::Player1Won) => 2,
+                (None, GameResult::Player2Won) => 3,
+                (None, GameResult::Draw) => 4,
+                (None, GameResult::NoContest) => 5,
+                (None, GameResult::Unknown) => 6,
+            }
+        }
+
+        match self {
+            SortColumn::Player1 => side_names(a, 0).cmp(&side_names(b, 0)),
+            SortColumn::Player2 => side_names(a, 1).cmp(&side_names(b, 1)),
+            SortColumn::Result => result_rank(a, me).cmp(&result_rank(b, me)),
+            SortColumn::Stage => a.stage_name.cmp(&b.stage_name),
+            SortColumn::Date => a.date.cmp(&b.date),
+            SortColumn::Duration => a.duration.cmp(&b.duration),
+            SortColumn::OpponentRank => opponent_rank_ordinal(a).cmp(&opponent_rank_ordinal(b)),
+        }
+    }
+}
+
+/// How many table rows are shown per page.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+enum PageSize {
+    Rows100,
+    #[default]
+    Rows500,
+    Rows1000,
+    All,
+}
+
+impl PageSize {
+    const ALL: [PageSize; 4] = [
+        PageSize::Rows100,
+        PageSize::Rows500,
+        PageSize::Rows1000,
+        PageSize::All,
+    ];
+
+    fn rows(self) -> Option<usize> {
+        match self {
+            PageSize::Rows100 => Some(100),
+            PageSize::Rows500 => Some(500),
+            PageSize::Rows1000 => Some(1000),
+            PageSize::All => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PageSize::Rows100 => "100",
+            PageSize::Rows500 => "500",
+            PageSize::Rows1000 => "1000",
+            PageSize::All => "All",
+        }
+    }
+}
+
+/// A row of the replays table.
+enum TableRow {
+    /// Header for a session: its key (see [`session_key`]) and matching replays.
+    Session(SessionKey, Vec<usize>),
+    /// A replay, by index into `replays`.
+    Replay(usize),
+}
+
+/// Identifies a session across rescans and new games: the file path and date of its first
+/// game. Replays without a file (sample data) still differ by date, and undated ones by path.
+type SessionKey = (String, Option<std::time::SystemTime>);
+
+/// The key of a session whose games, newest first, are `session`. Keyed by the oldest game,
+/// since new games join a session at the newest end.
+fn session_key(replays: &[ReplayInfo], session: &[usize]) -> SessionKey {
+    let first = &replays[session[session.len() - 1]];
+    (first.file_path.clone(), first.date)
+}
+
+/// The shown replays in the order the replays table lists them; see [`PlayerStats::order`].
+enum TableOrder {
+    /// Sorted by `column`.
+    Sorted {
+        column: SortColumn,
+        ascending: bool,
+        replays: Vec<usize>,
+    },
+    /// Grouped into sessions, in date order.
+    Sessions(Vec<(SessionKey, Vec<usize>)>),
+}
+
+impl TableOrder {
+    /// Whether this is still the order for the given grouping and sort.
+    fn fits(&self, group_by_session: bool, sort_column: SortColumn, sort_ascending: bool) -> bool {
+        match self {
+            TableOrder::Sorted {
+                column, ascending, ..
+            } => !group_by_session && *column == sort_column && *ascending == sort_ascending,
+            TableOrder::Sessions(_) => group_by_session,
+        }
+    }
+}
+
+/// Column the head-to-head sub-table is sorted by.
+#[derive(Clone, Copy, PartialEq)]
+enum HeadToHeadSort {
+    Opponent,
+    Games,
+    WinRate,
+}
+
+/// Open `path` with whatever the OS associates with it.
+fn open_with_os(path: &std::path::Path) -> std::io::Result<()> {
+    // Not `cmd /C start`: cmd re-parses the path, so `&` or `^` in a file name would run
+    // commands. explorer.exe takes it as a single argument.
+    let mut command = if cfg!(target_os = "windows") {
+        std::process::Command::new("explorer")
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command.arg(path).spawn().map(|_| ())
+}
+
+/// Show `path` in the OS file manager, selected where the platform supports it.
+fn reveal_in_file_manager(path: &std::path::Path) -> std::io::Result<()> {
+    if cfg!(target_os = "windows") {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        std::process::Command::new("explorer")
+            .arg(select)
+            .spawn()
+            .map(|_| ())
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+            .arg("-R")
+            .arg(path)
+            .spawn()
+            .map(|_| ())
+    } else {
+        // xdg-open can't select a file, so open the containing folder
+        open_with_os(path.parent().unwrap_or(path))
+    }
+}
+
+/// How many files or folders couldn't be read while scanning, e.g. "3 paths could not be read".
+fn unreadable_paths_text(count: usize) -> String {
+    match count {
+        1 => "1 path could not be read".to_string(),
+        n => format!("{n} paths could not be read"),
+    }
+}
+
+/// A win-loss record like "42-10 (80.8%)".
+fn record_text(wins: usize, losses: usize) -> String {
+    let games = wins + losses;
+    let win_rate = if games > 0 {
+        wins as f64 / games as f64 * 100.0
+    } else {
+        0.0
+    };
+    format!("{wins}-{losses} ({win_rate:.1}%)")
+}
+
+/// How long a toast notification stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+/// How many rank lookups "Lookup All Ranks" runs at once, to go easy on the Slippi API.
+const RANK_LOOKUP_CONCURRENCY: usize = 4;
+
+/// How long typing in the search box has to pause before the table is filtered again.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// How long a scan can go without a progress report before the status says so.
+const SCAN_STALL_NOTICE: Duration = Duration::from_secs(3);
+
+/// How long to wait for the screenshot a stat card is cut from. Some platforms never deliver
+/// one, and the card would otherwise stay on screen for good.
+const STAT_CARD_CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A replay scan running on a background thread.
+struct ScanTask {
+    result: mpsc::Receiver<Result<ReplayAnalyzer, String>>,
+    progress: mpsc::Receiver<ScanProgress>,
+    /// Replays streamed in as they're parsed, shown before the scan finishes.
+    replays: mpsc::Receiver<ReplayInfo>,
+    cancel: Arc<AtomicBool>,
+    /// Rescanning behind a restored replay list, which stays on screen until the scan
+    /// finishes instead of the table being cleared and streamed back in.
+    refresh: bool,
+    started: Instant,
+    last_heartbeat: Instant,
+    latest: Option<ScanProgress>,
+}
+
+impl ScanTask {
+    fn status_text(&self) -> String {
+        let progress = match self.latest {
+            Some(ScanProgress::Enumerating { found }) => {
+                format!("Scanning replays... ({found} found)")
+            }
+            Some(ScanProgress::Parsing { done, total }) => {
+                format!("Parsing replays... ({done}/{total})")
+            }
+            None => "Scanning replays...".to_string(),
+        };
+        if self.last_heartbeat.elapsed() >= SCAN_STALL_NOTICE {
+            format!(
+                "Still scanning... {}s elapsed. {progress}",
+                self.started.elapsed().as_secs()
+            )
+        } else {
+            progress
+        }
+    }
+}
+
+/// Win/loss numbers for the connect code, cached between frames. Recomputed when
+/// `Eppi::stats_dirty` is set or the connect code, nametag or search changes.
+#[derive(Default)]
+struct PlayerStats {
+    connect_code: String,
+    name_tag: String,
+    query: String,
+    wins: usize,
+    losses: usize,
+    draws: usize,
+    streak: i32,
+    longest_streak: usize,
+    /// Which replays pass the search and table filters, by index. The table is built from
+    /// this instead of filtering every frame.
+    shown: Vec<bool>,
+    /// The shown replays in table order, built on first use so the table isn't re-sorted
+    /// every frame. Rebuilt with the rest of the stats, or when the sort changes.
+    order: Option<TableOrder>,
+    /// Which replays are part of a run of wins in `order`, by index, so the borders mark
+    /// neighbouring rows. Built with `order`.
+    on_streak: Vec<bool>,
+    /// Total duration of those replays, in frames.
+    playtime_frames: u64,
+    /// Whether the connect code played in any loaded replay (true when none are loaded).
+    tag_found: bool,
+    /// Most common tag in the replays, offered when the connect code wasn't found.
+    suggested_tag: Option<String>,
+}
+
+/// We derive Deserialize/Serialize so we can persist app state on shutdown. Persisted fields
+/// must also be listed in [`LenientState`], so a corrupt value only resets itself.
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
 pub struct Eppi {
     connect_code: String,
+    /// In-game nametag to match in games where no player has `connect_code` (offline, or
+    /// netplay without a code). Empty to match on the connect code alone.
+    name_tag: String,
+    /// Directory typed into the box next to "Add", not yet in `replay_dirs`. Older versions
+    /// stored the one scanned directory here.
     replay_dir: String,
+    /// Every directory scanned for replays.
+    replay_dirs: Vec<String>,
 
     // Table demo fields
     demo: DemoType,
@@ -32,10 +477,98 @@ pub struct Eppi {
     num_rows: usize,
     scroll_to_row_slider: usize,
     scroll_to_row: Option<usize>,
-    selection: std::collections::HashSet<usize>,
+    /// Selected replays by file path, so selections survive sorting, paging and rescans.
+    selection: std::collections::HashSet<String>,
+    /// File path of the replay moved with the arrow keys, separate from the selection. By path
+    /// so it stays on the same replay when the rows are re-sorted or filtered.
+    #[serde(skip)]
+    focused_replay: Option<String>,
+    /// File path of the replay a range selection starts from: the last plain or Ctrl click, or
+    /// where Shift was first held with the arrow keys.
+    #[serde(skip)]
+    selection_anchor: Option<String>,
     checked: bool,
     reversed: bool,
 
+    /// Count draws and no-contests as non-wins when computing the win rate.
+    count_draws_in_win_rate: bool,
+    date_source: DateSource,
+    /// How many hours a looked-up rank is cached before it's fetched again.
+    rank_cache_ttl_hours: u64,
+    /// Replays table sort; newest first by default.
+    sort_column: SortColumn,
+    sort_ascending: bool,
+    /// Date order the table goes back to after a full scan; headers can still re-sort it.
+    default_sort_descending: bool,
+    /// Show the table as collapsible play sessions instead of one sorted list.
+    group_by_session: bool,
+    /// Only show games where both players picked the same character.
+    #[serde(skip)]
+    dittos_only: bool,
+    /// Only show ranked matchmaking games; games whose mode is unknown are left out.
+    #[serde(skip)]
+    ranked_only: bool,
+    /// Only show games on tournament-legal stages.
+    #[serde(skip)]
+    legal_stages_only: bool,
+    /// Only show games where the connect code played this character.
+    #[serde(skip)]
+    my_character: Option<u8>,
+    /// Show dates as absolute timestamps instead of "3 days ago".
+    absolute_dates: bool,
+    /// Leave out games shorter than `min_game_seconds`, e.g. handwarmers and accidental starts.
+    hide_short_games: bool,
+    min_game_seconds: u32,
+    page_size: PageSize,
+    /// Zero-based page of the table being shown.
+    #[serde(skip)]
+    page: usize,
+    /// How replay directories are walked, e.g. how deep.
+    scan_options: ScanOptions,
+    /// Add new replays from `replay_dirs` as Slippi finishes writing them.
+    watch_replay_dir: bool,
+    /// Program replays are opened with (e.g. Slippi Dolphin). Empty uses the OS default.
+    playback_path: String,
+    /// Where rank lookups are sent.
+    web_config: WebConfig,
+    /// Never touch the network: rank lookups only use the cache, stale entries included.
+    offline_mode: bool,
+    /// Replaces the slippi.gg lookups built from `web_config`, e.g. with canned ranks.
+    #[serde(skip)]
+    rank_provider: Option<Arc<dyn RankProvider>>,
+
+    #[serde(skip)]
+    show_settings: bool,
+    #[serde(skip)]
+    toast: Option<(String, Instant)>,
+    #[serde(skip)]
+    search_query: String,
+    /// `search_query` as last applied to the table, trimmed and lowercase.
+    #[serde(skip)]
+    last_query: String,
+    /// When `search_query` was last typed in, while the change waits on [`SEARCH_DEBOUNCE`].
+    #[serde(skip)]
+    search_edited_at: Option<Instant>,
+    #[serde(skip)]
+    selected_opponent: Option<String>,
+    /// Head-to-head sub-table sort column and whether it's ascending.
+    #[serde(skip)]
+    head_to_head_sort: (HeadToHeadSort, bool),
+    #[serde(skip)]
+    matchup_filter: String,
+    /// Replay whose frame data couldn't be read for the details window, and why.
+    #[serde(skip)]
+    details_error: Option<(String, String)>,
+    /// Auto-detected connect code and its replay count, waiting on confirmation to replace
+    /// the one entered.
+    #[serde(skip)]
+    detected_connect_code: Option<(String, usize)>,
+    /// Replay the user asked to delete, waiting on confirmation.
+    #[serde(skip)]
+    pending_delete: Option<String>,
+    /// Collapsed sessions, see [`session_key`].
+    #[serde(skip)]
+    collapsed_sessions: std::collections::HashSet<SessionKey>,
     #[serde(skip)]
     opened_file: Option<PathBuf>,
     #[serde(skip)]
@@ -43,6 +576,10 @@ pub struct Eppi {
     #[serde(skip)]
     open_dir_dialog: Option<FileDialog>,
     #[serde(skip)]
+    export_dialog: Option<(StatsExport, FileDialog)>,
+    #[serde(skip)]
+    stat_card_capture: Option<StatCardCapture>,
+    #[serde(skip)]
     replay_analyzer: ReplayAnalyzer,
     #[serde(skip)]
     is_scanning: bool,
@@ -51,18 +588,43 @@ pub struct Eppi {
     #[serde(skip)]
     is_fetching_rank: bool,
     #[serde(skip)]
-    rank_receiver: Option<mpsc::Receiver<(String, Result<String, String>)>>,
+    rank_receiver: Option<mpsc::Receiver<(String, Result<RankInfo, String>)>>,
+    #[serde(skip)]
+    scan_task: Option<ScanTask>,
+    /// New replays found by a "Refresh", see [`Self::refresh_replays`].
+    #[serde(skip)]
+    refresh_receiver: Option<mpsc::Receiver<NewReplays>>,
+    /// Progress reports from the running refresh.
     #[serde(skip)]
-    scan_receiver: Option<mpsc::Receiver<Result<ReplayAnalyzer, String>>>,
+    refresh_progress: Option<mpsc::Receiver<ScanProgress>>,
+    #[serde(skip)]
+    /// One watcher per entry in `replay_dirs` while watching is on.
+    watchers: Vec<ReplayWatcher>,
     #[serde(skip)]
     rank_icons: HashMap<String, TextureHandle>,
+    /// Stock icons by character id.
+    #[serde(skip)]
+    character_icons: HashMap<u8, TextureHandle>,
+    /// Stage thumbnails by stage id.
+    #[serde(skip)]
+    stage_icons: HashMap<u16, TextureHandle>,
+    #[serde(skip)]
+    stats: PlayerStats,
+    /// Set whenever the replay list changes so `stats` gets recomputed.
+    #[serde(skip)]
+    stats_dirty: bool,
+    /// The replay list changed since it was last saved for the next launch.
+    #[serde(skip)]
+    replays_dirty: bool,
 }
 
 impl Default for Eppi {
     fn default() -> Self {
         Self {
             connect_code: "".to_owned(),
+            name_tag: String::new(),
             replay_dir: "".to_owned(),
+            replay_dirs: Vec::new(),
             demo: DemoType::ReplayData,
             striped: true,
             overline: false,
@@ -72,18 +634,64 @@ impl Default for Eppi {
             scroll_to_row_slider: 0,
             scroll_to_row: None,
             selection: std::collections::HashSet::new(),
+            focused_replay: None,
+            selection_anchor: None,
             checked: false,
             reversed: false,
+            count_draws_in_win_rate: false,
+            date_source: DateSource::default(),
+            rank_cache_ttl_hours: DEFAULT_RANK_CACHE_TTL.as_secs() / 3_600,
+            sort_column: SortColumn::default(),
+            sort_ascending: false,
+            default_sort_descending: true,
+            group_by_session: false,
+            dittos_only: false,
+            ranked_only: false,
+            legal_stages_only: false,
+            hide_short_games: false,
+            min_game_seconds: 30,
+            my_character: None,
+            absolute_dates: false,
+            page_size: PageSize::default(),
+            page: 0,
+            scan_options: ScanOptions::default(),
+            watch_replay_dir: false,
+            playback_path: String::new(),
+            web_config: WebConfig::default(),
+            offline_mode: false,
+            rank_provider: None,
+            collapsed_sessions: std::collections::HashSet::new(),
+            show_settings: false,
+            toast: None,
+            search_query: String::new(),
+            last_query: String::new(),
+            search_edited_at: None,
+            selected_opponent: None,
+            head_to_head_sort: (HeadToHeadSort::Games, false),
+            matchup_filter: String::new(),
+            details_error: None,
+            detected_connect_code: None,
+            pending_delete: None,
             opened_file: None,
             open_file_dialog: None,
             open_dir_dialog: None,
-            replay_analyzer: ReplayAnalyzer::new(),
+            export_dialog: None,
+            stat_card_capture: None,
+            replay_analyzer: ReplayAnalyzer::with_cache(rank_cache_path()),
             is_scanning: false,
             scan_status: "Ready".to_string(),
             is_fetching_rank: false,
             rank_receiver: None,
-            scan_receiver: None,
+            refresh_receiver: None,
+            refresh_progress: None,
+            scan_task: None,
+            watchers: Vec::new(),
             rank_icons: HashMap::new(),
+            character_icons: HashMap::new(),
+            stage_icons: HashMap::new(),
+            stats: PlayerStats::default(),
+            stats_dirty: true,
+            replays_dirty: false,
         }
     }
 }
@@ -97,40 +705,128 @@ impl Eppi {
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
         let mut app = if let Some(storage) = cc.storage {
-            eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
+            eframe::get_value(storage, eframe::APP_KEY)
+                .or_else(|| Self::restore_leniently(storage))
+                .unwrap_or_default()
         } else {
             Self::default()
         };
 
         // Always start in replay data mode
         app.demo = DemoType::ReplayData;
+        app.connect_code = normalize_tag(&app.connect_code);
+        if app.replay_dirs.is_empty() && !app.replay_dir.is_empty() {
+            // Carry over the single directory saved by older versions
+            app.replay_dirs.push(std::mem::take(&mut app.replay_dir));
+        }
+        app.replay_dirs = app.replay_dirs.iter().map(|dir| expand_path(dir)).collect();
+        app.replay_analyzer.date_source = app.date_source;
+        app.sync_rank_cache_ttl();
+        app.sync_short_game_filter();
+
+        // Show the replays from last time right away, then pick up anything that changed
+        // while the app was closed
+        if app.replay_analyzer.load_replay_list() > 0 {
+            app.replay_analyzer
+                .fill_opponent_ranks(PlayerTag::new(&app.connect_code, &app.name_tag));
+            app.scan_replays(&cc.egui_ctx, true);
+        }
 
-        // Load rank icons
+        // Load rank, character and stage icons
         app.load_rank_icons(&cc.egui_ctx);
+        app.load_character_icons(&cc.egui_ctx);
+        app.load_stage_icons(&cc.egui_ctx);
 
         app
     }
 
-    fn scan_replays(&mut self, ctx: &egui::Context) {
-        if !self.replay_dir.is_empty() && !self.is_scanning {
+    /// Look ranks up with `provider` instead of slippi.gg.
+    pub fn set_rank_provider(&mut self, provider: Arc<dyn RankProvider>) {
+        self.rank_provider = Some(provider);
+    }
+
+    /// The provider set with [`Self::set_rank_provider`], or slippi.gg per the current settings.
+    fn rank_provider(&self) -> Arc<dyn RankProvider> {
+        self.rank_provider.clone().unwrap_or_else(|| {
+            Arc::new(SlippiRankProvider {
+                config: self.web_config.clone(),
+            })
+        })
+    }
+
+    /// Retry a failed restore field-by-field, keeping every value that still decodes.
+    fn restore_leniently(storage: &dyn eframe::Storage) -> Option<Self> {
+        storage.get_string(eframe::APP_KEY)?;
+        log::warn!("Stored app state failed to decode; recovering valid fields");
+
+        let state: LenientState = eframe::get_value(storage, eframe::APP_KEY)?;
+        let mut app = Self::default();
+        state.apply(&mut app);
+        Some(app)
+    }
+
+    /// Scan `replay_dirs` in the background. With `refresh`, the current replays stay shown
+    /// until the scan finishes; otherwise the table is cleared and fills in as replays parse.
+    fn scan_replays(&mut self, ctx: &egui::Context, refresh: bool) {
+        if let Some(dir) = missing_dir(&self.replay_dirs) {
+            self.scan_status = format!("Directory not found: {dir}");
+            return;
+        }
+        if !self.replay_dirs.is_empty() && !self.is_scanning {
             self.is_scanning = true;
             self.scan_status = "Scanning replays...".to_string();
 
-            // Create channel for async communication
+            // Channels for the result, progress/heartbeat reports and each parsed replay
             let (tx, rx) = mpsc::channel();
-            self.scan_receiver = Some(rx);
+            let (progress_tx, progress_rx) = mpsc::channel();
+            let (replay_tx, replay_rx) = mpsc::channel();
+            let cancel = Arc::new(AtomicBool::new(false));
+            let now = Instant::now();
+            if !refresh {
+                self.replay_analyzer.replays.clear();
+                self.stats_dirty = true;
+            }
+            self.scan_task = Some(ScanTask {
+                result: rx,
+                progress: progress_rx,
+                replays: replay_rx,
+                cancel: cancel.clone(),
+                refresh,
+                started: now,
+                last_heartbeat: now,
+                latest: None,
+            });
 
-            // Spawn async task for scanning
-            let replay_dir = self.replay_dir.clone();
+            // Scanning is blocking IO + rayon, so keep it off the async workers
+            let replay_dirs = self.replay_dirs.clone();
+            let date_source = self.date_source;
+            let scan_options = self.scan_options.clone();
             let ctx_clone = ctx.clone();
 
-            tokio::spawn(async move {
-                // Adding a small delay to make the spinner visible for testing
-                // tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-
+            tokio::task::spawn_blocking(move || {
+                let progress_ctx = ctx_clone.clone();
                 let mut analyzer = ReplayAnalyzer::new();
-                let result = match analyzer.scan_directory(&replay_dir) {
+                analyzer.date_source = date_source;
+                analyzer.scan_options = scan_options;
+                let scanned = analyzer.scan_directories(
+                    &replay_dirs,
+                    &cancel,
+                    |p| {
+                        if progress_tx.send(p).is_ok() {
+                            progress_ctx.request_repaint();
+                        }
+                    },
+                    |replay| {
+                        if replay_tx.send(replay.clone()).is_ok() {
+                            progress_ctx.request_repaint();
+                        }
+                    },
+                );
+                let result = match scanned {
                     Ok(_) => Ok(analyzer),
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                        Err("Scan cancelled".to_string())
+                    }
                     Err(e) => Err(format!("Error: {e}")),
                 };
 
@@ -143,25 +839,149 @@ impl Eppi {
         }
     }
 
+    /// Sort the table by date in the order chosen in Settings.
+    fn apply_default_sort(&mut self) {
+        self.sort_column = SortColumn::Date;
+        self.sort_ascending = !self.default_sort_descending;
+    }
+
+    /// Parse just the replays that aren't loaded yet, in the background. Much quicker than a
+    /// full scan between sets; deleted or changed files are only picked up by a scan.
+    fn refresh_replays(&mut self, ctx: &egui::Context) {
+        if self.replay_dirs.is_empty() || self.is_scanning || self.refresh_receiver.is_some() {
+            return;
+        }
+        if let Some(dir) = missing_dir(&self.replay_dirs) {
+            self.scan_status = format!("Directory not found: {dir}");
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        let (progress_tx, progress_rx) = mpsc::channel();
+        self.refresh_receiver = Some(rx);
+        self.refresh_progress = Some(progress_rx);
+        self.scan_status = "Looking for new replays...".to_string();
+
+        let replay_dirs = self.replay_dirs.clone();
+        let scan_options = self.scan_options.clone();
+        let known = self
+            .replay_analyzer
+            .replays
+            .iter()
+            .map(|replay| replay.file_path.clone())
+            .collect();
+        let ctx = ctx.clone();
+        tokio::task::spawn_blocking(move || {
+            let new_replays =
+                ReplayAnalyzer::find_new_replays(&replay_dirs, &scan_options, &known, |p| {
+                    if progress_tx.send(p).is_ok() {
+                        ctx.request_repaint();
+                    }
+                });
+            if tx.send(new_replays).is_ok() {
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    /// Start, restart or stop the directory watchers to match the settings.
+    fn sync_watcher(&mut self, ctx: &egui::Context) {
+        if !self.watch_replay_dir {
+            self.watchers.clear();
+            return;
+        }
+        let up_to_date = self.watchers.len() == self.replay_dirs.len()
+            && self
+                .watchers
+                .iter()
+                .zip(&self.replay_dirs)
+                .all(|(watcher, dir)| {
+                    watcher.dir() == std::path::Path::new(dir)
+                        && *watcher.options() == self.scan_options
+                });
+        if !up_to_date {
+            self.watchers = self
+                .replay_dirs
+                .iter()
+                .map(|dir| {
+                    let ctx = ctx.clone();
+                    ReplayWatcher::spawn(PathBuf::from(dir), self.scan_options.clone(), move || {
+                        ctx.request_repaint()
+                    })
+                })
+                .collect();
+        }
+    }
+
+    /// Fill in the most common tag in the replays as the connect code. An existing, different
+    /// code is only replaced once confirmed.
+    fn detect_connect_code(&mut self) {
+        let Some((tag, count)) = self.replay_analyzer.most_common_tag() else {
+            self.show_toast("No player tags found in the replays");
+            return;
+        };
+        let tag = tag.to_string();
+        if self.connect_code.is_empty() {
+            self.show_toast(format!("Detected {tag} (in {count} replays)"));
+            self.connect_code = tag;
+            self.connect_code_changed();
+        } else if same_tag(&self.connect_code, &tag) {
+            self.show_toast(format!("{tag} is already the most common tag"));
+        } else {
+            self.detected_connect_code = Some((tag, count));
+        }
+    }
+
+    /// Normalize a newly entered connect code and refresh everything that depends on it.
+    fn connect_code_changed(&mut self) {
+        self.connect_code = normalize_tag(&self.connect_code);
+        self.player_changed();
+    }
+
+    /// Refresh everything that depends on which player is "me".
+    fn player_changed(&mut self) {
+        let me = PlayerTag::new(&self.connect_code, &self.name_tag);
+        self.replay_analyzer.fill_opponent_ranks(me);
+        self.stats_dirty = true;
+    }
+
+    /// The player stats are for: the connect code, falling back to the nametag.
+    fn me(&self) -> PlayerTag<'_> {
+        PlayerTag::new(&self.connect_code, &self.name_tag)
+    }
+
+    fn add_replay_dir(&mut self, dir: String) {
+        let dir = expand_path(&dir);
+        if !self.replay_dirs.contains(&dir) {
+            self.replay_dirs.push(dir);
+        }
+    }
+
+    fn cancel_scan(&mut self) {
+        if let Some(task) = &self.scan_task {
+            task.cancel.store(true, Ordering::Relaxed);
+            self.scan_status = "Cancelling scan...".to_string();
+        }
+    }
+
     fn lookup_opponent_rank(&mut self, ctx: &egui::Context, opponent_tag: String) {
         if !self.is_fetching_rank {
             self.is_fetching_rank = true;
             self.scan_status = "Looking up opponent rank...".to_string();
 
-            // Check if we already have this opponent's rank cached
-            let cached_rank = self.replay_analyzer.get_cached_rank(&opponent_tag).cloned();
+            // Check if we already have this opponent's rank cached. Offline, a stale rank
+            // is better than none.
+            let cached_rank = if self.offline_mode {
+                self.replay_analyzer.get_cached_rank(&opponent_tag).cloned()
+            } else {
+                self.replay_analyzer.get_fresh_rank(&opponent_tag).cloned()
+            };
             if let Some(cached_rank) = cached_rank {
                 // Update all replays with this opponent with cached rank
                 for replay in &mut self.replay_analyzer.replays {
-                    let replay_opponent = if replay.player1.name == self.connect_code {
-                        &replay.player2.name
-                    } else if replay.player2.name == self.connect_code {
-                        &replay.player1.name
-                    } else {
-                        continue;
-                    };
-
-                    if replay_opponent == &opponent_tag {
+                    let against_opponent = replay
+                        .perspective(PlayerTag::new(&self.connect_code, &self.name_tag))
+                        .is_some_and(|(_, opponent)| same_tag(&opponent.name, &opponent_tag));
+                    if against_opponent {
                         replay.opponent_rank = Some(cached_rank.clone());
                     }
                 }
@@ -170,28 +990,154 @@ impl Eppi {
                 return;
             }
 
-            // Create channel for async communication
-            let (tx, rx) = mpsc::channel();
-            self.rank_receiver = Some(rx);
+            self.is_fetching_rank = false;
+            self.fetch_rank(ctx, opponent_tag);
+        }
+    }
 
-            // Spawn async task for web scraping
-            let ctx_clone = ctx.clone();
-            let opponent_tag_clone = opponent_tag.clone();
+    /// Look up `player_tag` on slippi.gg even if it's cached. The result arrives through
+    /// `rank_receiver` and lands in the cache.
+    fn fetch_rank(&mut self, ctx: &egui::Context, player_tag: String) {
+        if self.is_fetching_rank {
+            return;
+        }
+        if self.offline_mode {
+            self.scan_status = format!("Offline mode: rank for {player_tag} is Unknown");
+            return;
+        }
+        self.is_fetching_rank = true;
+
+        // Create channel for async communication
+        let (tx, rx) = mpsc::channel();
+        self.rank_receiver = Some(rx);
+
+        // Spawn async task for web scraping
+        let ctx_clone = ctx.clone();
+        let player_tag_clone = player_tag.clone();
+        let provider = self.rank_provider();
+
+        tokio::spawn(async move {
+            let result = match provider.fetch(&player_tag_clone).await {
+                Ok(rank) => Ok(rank),
+                Err(e) => Err(format!("Failed to fetch rank: {e}")),
+            };
+
+            // Send result through channel, hanging up so the UI knows the lookup is done
+            if tx.send((player_tag_clone, result)).is_ok() {
+                drop(tx);
+                // Request repaint to update UI with the result
+                ctx_clone.request_repaint();
+            }
+        });
 
-            tokio::spawn(async move {
-                let result = match crate::peppi::fetch_player_rank(&opponent_tag_clone).await {
-                    Ok(rank) => Ok(rank),
-                    Err(e) => Err(format!("Failed to fetch rank: {e}")),
-                };
+        self.scan_status = format!("Looking up rank for {player_tag}...");
+    }
 
-                // Send result through channel
-                if tx.send((opponent_tag_clone, result)).is_ok() {
-                    // Request repaint to update UI with the result
-                    ctx_clone.request_repaint();
+    fn open_export_dialog(&mut self, kind: StatsExport) {
+        if kind == StatsExport::Selected && self.selection.is_empty() {
+            self.scan_status = "No replays selected to export".to_string();
+            return;
+        }
+        let mut dialog = FileDialog::save_file(None).default_filename(kind.default_filename());
+        dialog.open();
+        self.export_dialog = Some((kind, dialog));
+    }
+
+    fn export_stats(&mut self, kind: StatsExport, path: &std::path::Path) {
+        let analyzer = &self.replay_analyzer;
+        let result = match kind {
+            StatsExport::Character => analyzer.export_character_stats_csv(self.me(), path),
+            StatsExport::Stage => analyzer.export_stage_stats_csv(self.me(), path),
+            StatsExport::Opponent => analyzer.export_opponent_stats_csv(self.me(), path),
+            StatsExport::Replays => analyzer.export_replays_json(path),
+            StatsExport::Selected => analyzer.export_selected_replays_json(&self.selection, path),
+            // Saved over the next few frames by `stat_card_capture`
+            StatsExport::StatCard => {
+                self.stat_card_capture = Some(StatCardCapture {
+                    path: path.to_path_buf(),
+                    rect: None,
+                    screenshot_requested: false,
+                    started: Instant::now(),
+                });
+                return;
+            }
+        };
+        self.scan_status = match result {
+            Ok(()) => format!("Exported stats to {}", path.display()),
+            Err(e) => format!("Failed to export stats: {e}"),
+        };
+    }
+
+    /// Fetch the rank of every opponent in the table that isn't freshly cached,
+    /// [`RANK_LOOKUP_CONCURRENCY`] at a time.
+    fn lookup_all_ranks(&mut self, ctx: &egui::Context) {
+        if self.is_fetching_rank || self.offline_mode {
+            return;
+        }
+        let mut tags: Vec<String> = self
+            .replay_analyzer
+            .replays
+            .iter()
+            .filter_map(|replay| replay.perspective(self.me()))
+            .map(|(_, opponent)| opponent.name.clone())
+            .filter(|tag| tag != "Unknown" && self.replay_analyzer.get_fresh_rank(tag).is_none())
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        if tags.is_empty() {
+            self.show_toast("Every opponent's rank is already cached".to_string());
+            return;
+        }
+
+        self.is_fetching_rank = true;
+        self.scan_status = format!("Looking up {} opponent ranks...", tags.len());
+        let (tx, rx) = mpsc::channel();
+        self.rank_receiver = Some(rx);
+
+        let ctx = ctx.clone();
+        let provider = self.rank_provider();
+        tokio::spawn(async move {
+            let results = crate::web::fetch_ranks_batch(
+                provider.as_ref(),
+                tags,
+                RANK_LOOKUP_CONCURRENCY,
+                |_, _| {},
+            )
+            .await;
+            for (tag, result) in results {
+                let result = result.map_err(|e| format!("Failed to fetch rank: {e}"));
+                if tx.send((tag, result)).is_err() {
+                    return;
                 }
-            });
+            }
+            drop(tx);
+            ctx.request_repaint();
+        });
+    }
 
-            self.scan_status = format!("Looking up rank for {opponent_tag}...");
+    /// Cache a finished rank lookup and copy it onto the replays against that opponent.
+    fn apply_rank_result(&mut self, opponent_tag: String, result: Result<RankInfo, String>) {
+        match result {
+            Ok(rank) => {
+                // Update all replays that have this opponent
+                for replay in &mut self.replay_analyzer.replays {
+                    let against_opponent = replay
+                        .perspective(PlayerTag::new(&self.connect_code, &self.name_tag))
+                        .is_some_and(|(_, opponent)| same_tag(&opponent.name, &opponent_tag));
+                    if against_opponent {
+                        replay.opponent_rank = Some(rank.clone());
+                    }
+                }
+                // Searches can match on rank, so the filtered stats may change
+                self.stats_dirty = true;
+                self.scan_status = format!("Found rank for {opponent_tag}: {rank}");
+                self.replay_analyzer.cache_rank(opponent_tag, rank);
+            }
+            Err(error_msg) => {
+                self.scan_status = format!("Failed to lookup rank for {opponent_tag}: {error_msg}");
+                // Cache the error to avoid retrying
+                self.replay_analyzer.cache_rank_failure(opponent_tag);
+            }
         }
     }
 
@@ -216,29 +1162,7 @@ impl Eppi {
 
     fn load_rank_icons(&mut self, ctx: &egui::Context) {
         // List of all rank names that might appear
-        let ranks = vec![
-            "Bronze 1",
-            "Bronze 2",
-            "Bronze 3",
-            "Silver 1",
-            "Silver 2",
-            "Silver 3",
-            "Gold 1",
-            "Gold 2",
-            "Gold 3",
-            "Platinum 1",
-            "Platinum 2",
-            "Platinum 3",
-            "Diamond 1",
-            "Diamond 2",
-            "Diamond 3",
-            "Master 1",
-            "Master 2",
-            "Master 3",
-            "Grandmaster",
-            "Unranked",
-            "Unknown",
-        ];
+        let ranks = RANK_TIERS.into_iter().chain(["Unranked", "Unknown"]);
 
         for rank in ranks {
             if let Some(icon_path) = Self::rank_to_icon_path(rank) {
@@ -266,72 +1190,252 @@ impl Eppi {
             }
         }
     }
+
+    /// Icon file for a character, e.g. `Mr. Game & Watch` -> `GAME_AND_WATCH.png`.
+    fn character_to_icon_path(character_id: u8) -> String {
+        let icon_name = character_id_to_name(character_id)
+            .to_uppercase()
+            .replace("MR. ", "")
+            .replace('&', "AND")
+            .replace(". ", "_")
+            .replace(' ', "_");
+        format!("assets/character-icons/{icon_name}.png")
+    }
+
+    fn load_character_icons(&mut self, ctx: &egui::Context) {
+        // Every playable character, Captain Falcon through Ganondorf
+        for character_id in 0..=25 {
+            let icon_path = Self::character_to_icon_path(character_id);
+            let Ok(png_bytes) = std::fs::read(&icon_path) else {
+                log::error!("Failed to read character icon file: {icon_path}");
+                continue;
+            };
+            match egui_extras::image::load_image_bytes(&png_bytes) {
+                Ok(color_image) => {
+                    let texture = ctx.load_texture(
+                        format!("character_{character_id}"),
+                        color_image,
+                        egui::TextureOptions::LINEAR,
+                    );
+                    self.character_icons.insert(character_id, texture);
+                }
+                Err(e) => {
+                    log::error!("Failed to load character icon {icon_path}: {e}");
+                }
+            }
+        }
+    }
+
+    fn load_stage_icons(&mut self, ctx: &egui::Context) {
+        // Stage ids from Fountain of Dreams (2) through Final Destination (32)
+        for stage_id in 2..=32 {
+            let icon_path = format!("assets/stages/{stage_id}.png");
+            // Not every stage has a thumbnail; those just show their name
+            let Ok(png_bytes) = std::fs::read(&icon_path) else {
+                continue;
+            };
+            match egui_extras::image::load_image_bytes(&png_bytes) {
+                Ok(color_image) => {
+                    let texture = ctx.load_texture(
+                        format!("stage_{stage_id}"),
+                        color_image,
+                        egui::TextureOptions::LINEAR,
+                    );
+                    self.stage_icons.insert(stage_id, texture);
+                }
+                Err(e) => {
+                    log::error!("Failed to load stage icon {icon_path}: {e}");
+                }
+            }
+        }
+    }
 }
 
 impl eframe::App for Eppi {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, eframe::APP_KEY, self);
+        if let Err(e) = self.replay_analyzer.save_rank_cache() {
+            log::warn!("Failed to save rank cache: {e}");
+        }
+        if self.replays_dirty {
+            match self.replay_analyzer.save_replay_list() {
+                Ok(()) => self.replays_dirty = false,
+                Err(e) => log::warn!("Failed to save replay list: {e}"),
+            }
+        }
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Check for scan results from async tasks
-        if let Some(receiver) = &self.scan_receiver {
-            if let Ok(result) = receiver.try_recv() {
+        // Check for scan progress and results from the background scan
+        if let Some(task) = &mut self.scan_task {
+            for progress in task.progress.try_iter() {
+                task.latest = Some(progress);
+                task.last_heartbeat = Instant::now();
+            }
+            if !task.cancel.load(Ordering::Relaxed) {
+                self.scan_status = task.status_text();
+            }
+            // Keep the elapsed time ticking even when the worker is quiet
+            ctx.request_repaint_after(Duration::from_millis(500));
+
+            let streamed = self.replay_analyzer.replays.len();
+            if task.refresh {
+                task.replays.try_iter().for_each(drop);
+            } else {
+                self.replay_analyzer.replays.extend(task.replays.try_iter());
+            }
+            if self.replay_analyzer.replays.len() > streamed {
+                // Resolve dates and keep the partial list newest-first
+                self.replay_analyzer
+                    .set_date_source(self.replay_analyzer.date_source);
+                self.stats_dirty = true;
+            }
+
+            let mut apply_default_sort = false;
+            if let Ok(result) = task.result.try_recv() {
                 match result {
-                    Ok(replay_analyzer) => {
-                        // Replace our analyzer with the one from the async task
-                        self.replay_analyzer = replay_analyzer;
+                    Ok(_) if task.refresh && task.cancel.load(Ordering::Relaxed) => {
                         self.scan_status =
-                            format!("Found {} replays", self.replay_analyzer.replays.len());
+                            "Scan cancelled; showing the replays from last time".to_string();
+                    }
+                    Ok(replay_analyzer) => {
+                        // Take the full replay list from the async task but keep our rank cache
+                        self.replay_analyzer.replays = replay_analyzer.replays;
+                        self.replay_analyzer.parse_failures = replay_analyzer.parse_failures;
+                        self.replay_analyzer.walk_errors = replay_analyzer.walk_errors;
+                        self.replay_analyzer.fill_opponent_ranks(PlayerTag::new(
+                            &self.connect_code,
+                            &self.name_tag,
+                        ));
+                        self.stats_dirty = true;
+                        self.replays_dirty = true;
+                        apply_default_sort = !task.refresh;
+                        self.scan_status = if task.cancel.load(Ordering::Relaxed) {
+                            format!(
+                                "Scan cancelled; kept the {} replays parsed so far",
+                                self.replay_analyzer.replays.len()
+                            )
+                        } else {
+                            let mut status = format!(
+                                "Found {} replays in {} directories",
+                                self.replay_analyzer.replays.len(),
+                                self.replay_dirs.len()
+                            );
+                            let walk_errors = self.replay_analyzer.walk_errors.len();
+                            if walk_errors > 0 {
+                                status.push_str("; ");
+                                status.push_str(&unreadable_paths_text(walk_errors));
+                            }
+                            status
+                        };
                     }
                     Err(error_msg) => {
                         self.scan_status = error_msg;
                     }
                 }
                 self.is_scanning = false;
-                self.scan_receiver = None; // Clear the receiver
+                self.scan_task = None;
+            }
+            if apply_default_sort {
+                self.apply_default_sort();
             }
         }
 
-        // Check for rank lookup results from async tasks
-        if let Some(receiver) = &self.rank_receiver {
-            if let Ok((opponent_tag, result)) = receiver.try_recv() {
-                match result {
-                    Ok(rank) => {
-                        // Update cache and all replays with this opponent
-                        self.replay_analyzer
-                            .rank_cache
-                            .insert(opponent_tag.clone(), rank.clone());
-
-                        // Update all replays that have this opponent
-                        for replay in &mut self.replay_analyzer.replays {
-                            let replay_opponent = if replay.player1.name == self.connect_code {
-                                &replay.player2.name
-                            } else if replay.player2.name == self.connect_code {
-                                &replay.player1.name
-                            } else {
-                                continue;
-                            };
-
-                            if replay_opponent == &opponent_tag {
-                                replay.opponent_rank = Some(rank.clone());
-                            }
-                        }
-                        self.scan_status = format!("Found rank for {opponent_tag}: {rank}");
+        if let Some(progress) = &self.refresh_progress {
+            if let Some(latest) = progress.try_iter().last() {
+                self.scan_status = match latest {
+                    ScanProgress::Enumerating { found } => {
+                        format!("Looking for new replays... ({found} found)")
                     }
-                    Err(error_msg) => {
-                        // Cache the error to avoid retrying
-                        self.replay_analyzer
-                            .rank_cache
-                            .insert(opponent_tag.clone(), "Unranked".to_string());
-                        self.scan_status =
-                            format!("Failed to lookup rank for {opponent_tag}: {error_msg}");
+                    ScanProgress::Parsing { done, total } => {
+                        format!("Parsing new replays... ({done}/{total})")
+                    }
+                };
+            }
+        }
+        if let Some(receiver) = &self.refresh_receiver {
+            match receiver.try_recv() {
+                Ok(new_replays) => {
+                    let added = self.replay_analyzer.merge_replays(new_replays.replays);
+                    let walk_errors = new_replays.walk_errors.len();
+                    self.replay_analyzer.walk_errors = new_replays.walk_errors;
+                    if added > 0 {
+                        self.replay_analyzer.fill_opponent_ranks(PlayerTag::new(
+                            &self.connect_code,
+                            &self.name_tag,
+                        ));
+                        self.stats_dirty = true;
+                        self.replays_dirty = true;
+                    }
+                    self.scan_status = format!("Added {added} new replay(s)");
+                    if walk_errors > 0 {
+                        self.scan_status.push_str("; ");
+                        self.scan_status
+                            .push_str(&unreadable_paths_text(walk_errors));
                     }
+                    self.refresh_receiver = None;
+                    self.refresh_progress = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.scan_status = "Refresh failed".to_string();
+                    self.refresh_receiver = None;
+                    self.refresh_progress = None;
                 }
-                self.is_fetching_rank = false;
-                self.rank_receiver = None; // Clear the receiver
+            }
+        }
+
+        self.sync_watcher(ctx);
+        let mut added = 0;
+        for watcher in &self.watchers {
+            for mut replay in watcher.new_replays() {
+                // Nested directories both see the same new file
+                let replays = &mut self.replay_analyzer.replays;
+                if replays.iter().any(|r| r.file_path == replay.file_path) {
+                    continue;
+                }
+                replay.date = self.replay_analyzer.date_source.resolve(&replay);
+                replays.insert(0, replay);
+                added += 1;
+            }
+            for error in watcher.new_walk_errors() {
+                if !self.replay_analyzer.walk_errors.contains(&error) {
+                    log::warn!("Couldn't read {} while watching: {}", error.0, error.1);
+                    self.replay_analyzer.walk_errors.push(error);
+                }
+            }
+        }
+        if added > 0 {
+            self.replay_analyzer
+                .fill_opponent_ranks(PlayerTag::new(&self.connect_code, &self.name_tag));
+            self.stats_dirty = true;
+            self.replays_dirty = true;
+            self.scan_status = format!("Added {added} new replay(s)");
+        }
+
+        // Check for rank lookup results from async tasks. A batch lookup sends several results
+        // over the same channel; the lookup is done once the sender hangs up.
+        if let Some(receiver) = self.rank_receiver.take() {
+            let mut received = 0;
+            let finished = loop {
+                match receiver.try_recv() {
+                    Ok((opponent_tag, result)) => {
+                        received += 1;
+                        self.apply_rank_result(opponent_tag, result);
+                    }
+                    Err(mpsc::TryRecvError::Empty) => break false,
+                    Err(mpsc::TryRecvError::Disconnected) => break true,
+                };
+            };
+            if received > 1 {
+                self.scan_status = format!("Looked up {received} opponent ranks");
+            }
+            if finished {
+                self.is_fetching_rank = false;
+            } else {
+                self.rank_receiver = Some(receiver);
             }
         }
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
@@ -352,40 +1456,190 @@ impl eframe::App for Eppi {
                     ui.add_space(16.0);
                 }
 
+                let has_stats = !self.me().is_empty();
+                ui.add_enabled_ui(!self.replay_analyzer.replays.is_empty(), |ui| {
+                    ui.menu_button("Export", |ui| {
+                        for (label, kind, enabled) in [
+                            (
+                                "Character stats (CSV)...",
+                                StatsExport::Character,
+                                has_stats,
+                            ),
+                            ("Stage stats (CSV)...", StatsExport::Stage, has_stats),
+                            ("Opponent stats (CSV)...", StatsExport::Opponent, has_stats),
+                            ("All replays (JSON)...", StatsExport::Replays, true),
+                            ("Selected replays (JSON)...", StatsExport::Selected, true),
+                            ("Stat card (PNG)...", StatsExport::StatCard, has_stats),
+                        ] {
+                            if ui.add_enabled(enabled, egui::Button::new(label)).clicked() {
+                                self.open_export_dialog(kind);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+                ui.add_space(16.0);
+
+                if ui.button("Settings").clicked() {
+                    self.show_settings = !self.show_settings;
+                }
+                ui.add_space(16.0);
+
                 egui::widgets::global_theme_preference_buttons(ui);
             });
         });
 
+        self.settings_window(ctx);
+        self.opponent_window(ctx);
+        self.toast_ui(ctx);
+        self.breakdown_panel(ctx);
+        self.details_window(ctx);
+        self.delete_confirm_window(ctx);
+        self.stat_card_capture(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // The central panel the region left after adding TopPanel's and SidePanel's
             ui.horizontal(|ui| {
                 ui.label("My Connect Code:");
-                ui.text_edit_singleline(&mut self.connect_code);
+                if ui.text_edit_singleline(&mut self.connect_code).changed() {
+                    self.connect_code_changed();
+                    self.detected_connect_code = None;
+                }
+                ui.label("Nametag:");
+                if ui
+                    .add(egui::TextEdit::singleline(&mut self.name_tag).desired_width(60.0))
+                    .on_hover_text(
+                        "Your in-game nametag, matched in games where nobody has your connect \
+                         code (offline, or netplay without a code)",
+                    )
+                    .changed()
+                {
+                    self.player_changed();
+                }
+                if ui
+                    .add_enabled(
+                        !self.replay_analyzer.replays.is_empty(),
+                        egui::Button::new("Auto-detect"),
+                    )
+                    .on_hover_text("Use the tag that appears in the most replays")
+                    .clicked()
+                {
+                    self.detect_connect_code();
+                }
+                // Ask before replacing a code that was typed in
+                if let Some((tag, count)) = self.detected_connect_code.clone() {
+                    ui.label(format!("Use {tag} (in {count} replays)?"));
+                    if ui.button("Replace").clicked() {
+                        self.connect_code = tag;
+                        self.connect_code_changed();
+                        self.detected_connect_code = None;
+                    }
+                    if ui.button("Keep").clicked() {
+                        self.detected_connect_code = None;
+                    }
+                }
+
+                // My Rank: cached under the connect code like any other player
+                if !self.connect_code.is_empty() {
+                    ui.separator();
+                    ui.label("My Rank:");
+                    match self.replay_analyzer.get_cached_rank(&self.connect_code) {
+                        Some(rank) => {
+                            if let Some(icon) = self.rank_icons.get(&rank.rank) {
+                                ui.add(
+                                    egui::Image::from_texture(icon)
+                                        .max_size(egui::Vec2::new(20.0, 20.0)),
+                                );
+                            }
+                            ui.label(rank.label()).on_hover_text(rank.summary());
+                        }
+                        None => {
+                            ui.colored_label(egui::Color32::GRAY, "Not looked up");
+                        }
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.is_fetching_rank && !self.offline_mode,
+                            egui::Button::new("⟳").small(),
+                        )
+                        .on_hover_text("Look up your current rank")
+                        .clicked()
+                    {
+                        self.fetch_rank(ctx, self.connect_code.clone());
+                    }
+                }
             });
 
+            let mut dir_to_remove = None;
+            for (i, dir) in self.replay_dirs.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(if i == 0 { "Replay Directories:" } else { "" });
+                    ui.monospace(dir);
+                    if ui
+                        .add_enabled(!self.is_scanning, egui::Button::new("✖").small())
+                        .on_hover_text("Stop scanning this directory")
+                        .clicked()
+                    {
+                        dir_to_remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = dir_to_remove {
+                self.replay_dirs.remove(i);
+            }
+
             ui.horizontal(|ui| {
-                ui.label("Replays Directory:");
+                ui.label("Add Directory:");
                 ui.text_edit_singleline(&mut self.replay_dir);
+                if ui
+                    .add_enabled(!self.replay_dir.trim().is_empty(), egui::Button::new("Add"))
+                    .clicked()
+                {
+                    let dir = std::mem::take(&mut self.replay_dir);
+                    self.add_replay_dir(dir);
+                }
                 if ui.button("Browse...").clicked() {
-                    let initial_path = if self.replay_dir.is_empty() {
-                        None
-                    } else {
-                        Some(self.replay_dir.clone().into())
-                    };
+                    let initial_path = self.replay_dirs.last().map(PathBuf::from);
                     let mut dialog = FileDialog::select_folder(initial_path);
                     dialog.open();
                     self.open_dir_dialog = Some(dialog);
                 }
 
-                ui.add_enabled_ui(!self.is_scanning && !self.replay_dir.is_empty(), |ui| {
+                ui.add_enabled_ui(!self.is_scanning && !self.replay_dirs.is_empty(), |ui| {
                     if ui.button("Scan Replays").clicked() {
-                        self.scan_replays(ctx);
+                        self.scan_replays(ctx, false);
+                    }
+                    if ui
+                        .add_enabled(
+                            self.refresh_receiver.is_none(),
+                            egui::Button::new("Refresh"),
+                        )
+                        .on_hover_text("Only parse replays that aren't loaded yet")
+                        .clicked()
+                    {
+                        self.refresh_replays(ctx);
                     }
                 });
-
-                // Show a loading spinner while scanning replays, similar to the opponent-rank lookup flow
-                if self.is_scanning {
-                    ui.spinner();
+                ui.checkbox(&mut self.watch_replay_dir, "Watch")
+                    .on_hover_text("Add new replays automatically as games finish");
+
+                // Show parse progress while scanning replays; a spinner until the file count is known
+                if let Some(task) = &self.scan_task {
+                    match task.latest {
+                        Some(ScanProgress::Parsing { done, total }) if total > 0 => {
+                            ui.add(
+                                egui::ProgressBar::new(done as f32 / total as f32)
+                                    .desired_width(160.0)
+                                    .show_percentage(),
+                            );
+                        }
+                        _ => {
+                            ui.spinner();
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.cancel_scan();
+                    }
                 }
             });
 
@@ -401,60 +1655,1685 @@ impl eframe::App for Eppi {
                 }
             });
 
+            let walk_errors = &self.replay_analyzer.walk_errors;
+            if !walk_errors.is_empty() {
+                egui::CollapsingHeader::new(unreadable_paths_text(walk_errors.len()))
+                    .id_salt("walk_errors")
+                    .show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .id_salt("walk_errors_scroll")
+                            .max_height(150.0)
+                            .show(ui, |ui| {
+                                egui::Grid::new("walk_errors_grid")
+                                    .striped(true)
+                                    .show(ui, |ui| {
+                                        for (path, error) in walk_errors {
+                                            ui.monospace(path);
+                                            ui.colored_label(egui::Color32::LIGHT_RED, error);
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                    });
+            }
+
+            let failures = &self.replay_analyzer.parse_failures;
+            if !failures.is_empty() {
+                egui::CollapsingHeader::new(format!("{} files failed to parse", failures.len()))
+                    .id_salt("parse_failures")
+                    .show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(150.0)
+                            .show(ui, |ui| {
+                                egui::Grid::new("parse_failures_grid").striped(true).show(
+                                    ui,
+                                    |ui| {
+                                        for (path, error) in failures {
+                                            ui.monospace(path);
+                                            ui.colored_label(egui::Color32::LIGHT_RED, error);
+                                            ui.end_row();
+                                        }
+                                    },
+                                );
+                            });
+                    });
+            }
+
             if let Some(dialog) = &mut self.open_dir_dialog {
                 if dialog.show(ctx).selected() {
                     if let Some(path) = dialog.path() {
-                        self.replay_dir = path.to_string_lossy().to_string();
+                        let dir = path.to_string_lossy().to_string();
+                        self.add_replay_dir(dir);
                     }
                 }
             }
 
+            let mut export_target = None;
+            if let Some((kind, dialog)) = &mut self.export_dialog {
+                if dialog.show(ctx).selected() {
+                    if let Some(path) = dialog.path() {
+                        export_target = Some((*kind, path.to_path_buf()));
+                    }
+                }
+            }
+            if let Some((kind, path)) = export_target {
+                self.export_stats(kind, &path);
+                self.export_dialog = None;
+            }
+
             ui.separator();
 
             self.replays_table(ui, ctx);
 
             egui::warn_if_debug_build(ui);
+            if cfg!(debug_assertions)
+                && ui
+                    .add_enabled(!self.is_scanning, egui::Button::new("Load sample data"))
+                    .on_hover_text("Replace the table with synthetic replays (debug builds only)")
+                    .clicked()
+            {
+                self.replay_analyzer.load_sample_data();
+                self.stats_dirty = true;
+                if self.connect_code.is_empty() {
+                    self.connect_code = SAMPLE_CONNECT_CODE.to_string();
+                }
+                self.scan_status = format!(
+                    "Loaded {} sample replays",
+                    self.replay_analyzer.replays.len()
+                );
+            }
         });
     }
 }
 
 impl Eppi {
+    /// Briefly show `message` in the corner of the window.
+    fn show_toast(&mut self, message: impl Into<String>) {
+        self.toast = Some((message.into(), Instant::now()));
+    }
+
+    /// Per-player stats for the selected replay, when exactly one is selected. The per-frame
+    /// numbers are computed the first time it's shown.
+    fn details_window(&mut self, ctx: &egui::Context) {
+        let [selected] = self.selection.iter().collect::<Vec<_>>()[..] else {
+            return;
+        };
+        let Some(replay) = self
+            .replay_analyzer
+            .replays
+            .iter_mut()
+            .find(|replay| replay.file_path == *selected)
+        else {
+            return;
+        };
+        // Don't retry a replay whose frames already failed to load
+        let failed_before = matches!(&self.details_error, Some((path, _)) if path == selected);
+        if !replay.frames_analyzed && !failed_before {
+            if let Err(e) = replay.analyze_frames() {
+                self.details_error = Some((selected.clone(), e.to_string()));
+            }
+        }
+        let frame_error = match &self.details_error {
+            Some((path, e)) if path == selected && !replay.frames_analyzed => Some(e.as_str()),
+            _ => None,
+        };
+
+        let mut replay_to_open = None;
+        let mut open = true;
+        egui::Window::new("Replay details")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} · {}", replay.stage_name, replay.file_path));
+                    if ui
+                        .add_enabled(
+                            !replay.file_path.is_empty(),
+                            egui::Button::new("Open in Slippi"),
+                        )
+                        .clicked()
+                    {
+                        replay_to_open = Some(replay.file_path.clone());
+                    }
+                });
+                if let Some(e) = frame_error {
+                    ui.colored_label(egui::Color32::GRAY, format!("Frame data unavailable: {e}"));
+                }
+                let openings: Option<Vec<String>> = replay
+                    .players
+                    .iter()
+                    .map(|player| player.openings.map(|count| count.to_string()))
+                    .collect();
+                if let Some(openings) = openings {
+                    ui.label(format!("Openings: {}", openings.join(" / ")))
+                        .on_hover_text(
+                            "Conversions each player started, in port order. A hit is a new \
+                             opening once the player hit has been out of hitstun and grabs for \
+                             45 frames.",
+                        );
+                }
+                let first_hits: Option<Vec<u32>> = replay
+                    .players
+                    .iter()
+                    .map(|player| player.first_hits)
+                    .collect();
+                if let Some(first_hits) = first_hits {
+                    let stocks: u32 = first_hits.iter().sum();
+                    if stocks > 0 {
+                        let rates: Vec<String> = first_hits
+                            .iter()
+                            .map(|&hits| format!("{:.0}%", hits as f32 / stocks as f32 * 100.0))
+                            .collect();
+                        ui.label(format!("Neutral wins: {}", rates.join(" / ")))
+                            .on_hover_text(format!(
+                                "Share of the {stocks} stocks where each player landed the \
+                                 first hit, in port order"
+                            ));
+                    }
+                }
+                egui::Grid::new("replay_details")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Player");
+                        ui.strong("Character");
+                        ui.strong("APM");
+                        ui.strong("L-cancel");
+                        ui.strong("Damage dealt");
+                        ui.end_row();
+                        for player in &replay.players {
+                            ui.label(&player.name);
+                            ui.label(&player.character_name);
+                            ui.label(
+                                player
+                                    .apm
+                                    .map_or("-".to_string(), |apm| format!("{apm:.0}")),
+                            );
+                            ui.label(player.l_cancels.map_or(
+                                "-".to_string(),
+                                |(successful, attempts)| {
+                                    format!(
+                                        "{:.0}% ({successful}/{attempts})",
+                                        successful as f32 / attempts as f32 * 100.0
+                                    )
+                                },
+                            ));
+                            ui.label(
+                                player
+                                    .damage_dealt
+                                    .map_or("-".to_string(), |damage| format!("{damage:.0}%")),
+                            );
+                            ui.end_row();
+                        }
+                    });
+            });
+        if !open {
+            self.selection.clear();
+        }
+        if let Some(file_path) = replay_to_open {
+            self.open_replay(&file_path);
+        }
+    }
+
+    /// Launch a replay with the configured playback program, or the OS default for `.slp`
+    /// files. Failures go to the status line.
+    fn open_replay(&mut self, file_path: &str) {
+        if file_path.is_empty() {
+            self.scan_status = "This replay has no file to open".to_string();
+            return;
+        }
+        let result = if self.playback_path.trim().is_empty() {
+            open_with_os(std::path::Path::new(file_path))
+        } else {
+            std::process::Command::new(self.playback_path.trim())
+                .arg(file_path)
+                .spawn()
+                .map(|_| ())
+        };
+        self.scan_status = match result {
+            Ok(()) => format!("Opened {file_path}"),
+            Err(e) => format!("Failed to open {file_path}: {e}"),
+        };
+    }
+
+    /// Show the file containing a replay in the system file manager.
+    fn reveal_replay(&mut self, file_path: &str) {
+        if let Err(e) = reveal_in_file_manager(std::path::Path::new(file_path)) {
+            self.scan_status = format!("Failed to reveal {file_path}: {e}");
+        }
+    }
+
+    fn delete_confirm_window(&mut self, ctx: &egui::Context) {
+        let Some(file_path) = self.pending_delete.clone() else {
+            return;
+        };
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new("Delete replay")
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Permanently delete this replay?");
+                ui.monospace(&file_path);
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Delete").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+        if confirmed {
+            self.pending_delete = None;
+            match std::fs::remove_file(&file_path) {
+                Ok(()) => {
+                    self.replay_analyzer
+                        .replays
+                        .retain(|replay| replay.file_path != file_path);
+                    self.selection.remove(&file_path);
+                    self.stats_dirty = true;
+                    self.replays_dirty = true;
+                    self.scan_status = format!("Deleted {file_path}");
+                }
+                Err(e) => self.scan_status = format!("Failed to delete {file_path}: {e}"),
+            }
+        } else if cancelled {
+            self.pending_delete = None;
+        }
+    }
+
+    fn toast_ui(&mut self, ctx: &egui::Context) {
+        let Some((message, shown_at)) = &self.toast else {
+            return;
+        };
+        let remaining = TOAST_DURATION.saturating_sub(shown_at.elapsed());
+        if remaining.is_zero() {
+            self.toast = None;
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("toast"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-16.0, -16.0])
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(message.as_str());
+                });
+            });
+        ctx.request_repaint_after(remaining);
+    }
+
+    fn settings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("settings_grid")
+                    .num_columns(2)
+                    .spacing([16.0, 8.0])
+                    .show(ui, |ui| {
+                        ui.label("Replay date").on_hover_text(
+                            "The start time recorded in the replay is accurate but missing from \
+                             very old replays. The file's modified date is always available but \
+                             changes when replays are copied or synced between machines.",
+                        );
+                        let before = self.date_source;
+                        egui::ComboBox::from_id_salt("date_source")
+                            .selected_text(self.date_source.label())
+                            .show_ui(ui, |ui| {
+                                for source in DateSource::ALL {
+                                    ui.selectable_value(
+                                        &mut self.date_source,
+                                        source,
+                                        source.label(),
+                                    );
+                                }
+                            });
+                        if self.date_source != before {
+                            self.replay_analyzer.set_date_source(self.date_source);
+                            self.stats_dirty = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Default order").on_hover_text(
+                            "How the table is sorted after a scan. Click a column header to \
+                             sort differently.",
+                        );
+                        let before = self.default_sort_descending;
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut self.default_sort_descending, true, "Newest first");
+                            ui.radio_value(&mut self.default_sort_descending, false, "Oldest first");
+                        });
+                        if self.default_sort_descending != before {
+                            self.apply_default_sort();
+                        }
+                        ui.end_row();
+
+                        ui.label("Playback program").on_hover_text(
+                            "Program used by \"Open in Slippi\", e.g. Slippi Dolphin. Leave empty \
+                             to open replays with the system default.",
+                        );
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.playback_path)
+                                .hint_text("System default"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Short game length").on_hover_text(
+                            "Games shorter than this are hidden by \"Hide games under\"",
+                        );
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut self.min_game_seconds)
+                                    .range(1..=600)
+                                    .suffix(" s"),
+                            )
+                            .changed()
+                            && self.hide_short_games
+                        {
+                            self.sync_short_game_filter();
+                            self.filters_changed();
+                        }
+                        ui.end_row();
+
+                        ui.label("Offline mode").on_hover_text(
+                            "Only show cached ranks and never look ranks up, e.g. without a \
+                             connection.",
+                        );
+                        ui.checkbox(&mut self.offline_mode, "Don't look up ranks");
+                        ui.end_row();
+
+                        ui.label("Rank API endpoint").on_hover_text(
+                            "Slippi GraphQL URL used for rank lookups. Change it to go through a \
+                             proxy or to test against a mock server.",
+                        );
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.web_config.endpoint);
+                            if ui
+                                .add_enabled(
+                                    self.web_config != WebConfig::default(),
+                                    egui::Button::new("Reset"),
+                                )
+                                .clicked()
+                            {
+                                self.web_config = WebConfig::default();
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Rank API authorization").on_hover_text(
+                            "Sent as the Authorization header with rank lookups, e.g. for a proxy. \
+                             Leave empty for slippi.gg.",
+                        );
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.web_config.authorization)
+                                .password(true)
+                                .hint_text("None"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Rank cache lifetime").on_hover_text(
+                            "Ranks older than this are looked up again. Failed lookups are \
+                             retried after an hour at most.",
+                        );
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut self.rank_cache_ttl_hours)
+                                    .range(1..=24 * 30)
+                                    .suffix(" h"),
+                            )
+                            .changed()
+                        {
+                            self.sync_rank_cache_ttl();
+                        }
+                        ui.end_row();
+
+                        ui.label("Scan depth").on_hover_text(
+                            "How many folders deep to look for replays. Slippi's own layout \
+                             needs 2; a limit speeds up scans inside large synced folders.",
+                        );
+                        ui.horizontal(|ui| {
+                            let mut limited = self.scan_options.max_depth.is_some();
+                            if ui.checkbox(&mut limited, "Limit to").changed() {
+                                self.scan_options.max_depth = limited.then_some(2);
+                            }
+                            let mut depth = self.scan_options.max_depth.unwrap_or(2);
+                            if ui
+                                .add_enabled(
+                                    limited,
+                                    egui::DragValue::new(&mut depth)
+                                        .range(1..=32)
+                                        .suffix(" levels"),
+                                )
+                                .changed()
+                            {
+                                self.scan_options.max_depth = Some(depth);
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Hidden folders to scan").on_hover_text(
+                            "Hidden and system folders (like .git or $RECYCLE.BIN) are skipped. \
+                             List folder names here, separated by commas, to scan them anyway.",
+                        );
+                        // Names are kept as typed (spaces and all) so the text round-trips while
+                        // editing; they're trimmed when matched
+                        let mut include_hidden = self.scan_options.include_hidden.join(",");
+                        if ui
+                            .add(egui::TextEdit::singleline(&mut include_hidden).hint_text("None"))
+                            .changed()
+                        {
+                            self.scan_options.include_hidden = if include_hidden.is_empty() {
+                                Vec::new()
+                            } else {
+                                include_hidden.split(',').map(str::to_string).collect()
+                            };
+                        }
+                        ui.end_row();
+
+                        ui.label("Caches");
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Clear parse cache")
+                                .on_hover_text("Re-parse every replay on the next scan, retrying ones that failed")
+                                .clicked()
+                            {
+                                let message = match clear_parse_cache() {
+                                    Ok(removed) => {
+                                        format!("Cleared {removed} entries from the parse cache")
+                                    }
+                                    Err(e) => format!("Failed to clear parse cache: {e}"),
+                                };
+                                self.show_toast(message);
+                            }
+                            if ui
+                                .button("Clear rank cache")
+                                .on_hover_text("Re-fetch ranks on the next lookup")
+                                .clicked()
+                            {
+                                let removed = self.replay_analyzer.clear_rank_cache();
+                                self.show_toast(format!(
+                                    "Cleared {removed} entries from the rank cache"
+                                ));
+                            }
+                        });
+                        ui.end_row();
+                    });
+            });
+        self.show_settings = open;
+    }
+
+    fn sync_rank_cache_ttl(&mut self) {
+        let hours = self.rank_cache_ttl_hours.max(1);
+        self.replay_analyzer.cache_ttl = Duration::from_secs(hours * 3_600);
+    }
+
+    /// Leave games under the short game length out of the streaks and breakdowns too, not just
+    /// the table, while "Hide games under" is on.
+    fn sync_short_game_filter(&mut self) {
+        self.replay_analyzer.min_game_frames = self
+            .hide_short_games
+            .then_some(self.min_game_seconds as i32 * 60);
+    }
+
+    /// Recompute the cached W/L stats if the replays, connect code or search changed.
+    fn refresh_stats(&mut self) {
+        let query = self.last_query.clone();
+        if !self.stats_dirty
+            && self.stats.connect_code == self.connect_code
+            && self.stats.name_tag == self.name_tag
+            && self.stats.query == query
+        {
+            return;
+        }
+
+        // W/L follows the search and table filters
+        let analyzer = &self.replay_analyzer;
+        let code = self.me();
+        let (mut wins, mut losses, mut draws) = (0, 0, 0);
+        let mut shown = vec![false; analyzer.replays.len()];
+        let mut playtime_frames = 0;
+        for (i, replay) in analyzer.replays.iter().enumerate() {
+            if !self.shows_replay(replay, &query) {
+                continue;
+            }
+            shown[i] = true;
+            playtime_frames += replay.duration.map_or(0, |frames| frames.max(0) as u64);
+            match replay.won_by(code) {
+                Some(true) => wins += 1,
+                Some(false) => losses += 1,
+                None if replay.result.is_draw_or_no_contest()
+                    && replay.perspective(code).is_some() =>
+                {
+                    draws += 1
+                }
+                None => {}
+            }
+        }
+        let tag_found = analyzer.replays.is_empty() || analyzer.played_in_any(code);
+        let suggested_tag = if tag_found {
+            None
+        } else {
+            analyzer.most_common_tag().map(|(tag, _)| tag.to_string())
+        };
+        self.stats = PlayerStats {
+            tag_found,
+            suggested_tag,
+            wins,
+            losses,
+            draws,
+            streak: analyzer.current_streak(code),
+            longest_streak: analyzer.longest_win_streak(code),
+            shown,
+            order: None,
+            on_streak: Vec::new(),
+            playtime_frames,
+            connect_code: self.connect_code.clone(),
+            name_tag: self.name_tag.clone(),
+            query,
+        };
+        self.stats_dirty = false;
+    }
+
+    /// Plain-text record for pasting elsewhere: overall record, streak and the three most played
+    /// matchups, over the games the current search and filters show.
+    fn stats_summary(&self) -> String {
+        let code = &self.connect_code;
+        let PlayerStats {
+            wins,
+            losses,
+            draws,
+            streak,
+            ..
+        } = self.stats;
+        let mut summary = format!("{code}: {}", record_text(wins, losses));
+        if draws > 0 {
+            summary.push_str(&format!(", {draws} draws/NC"));
+        }
+        match streak {
+            s if s > 0 => summary.push_str(&format!("\nStreak: W{s}")),
+            s if s < 0 => summary.push_str(&format!("\nStreak: L{}", -s)),
+            _ => {}
+        }
+
+        let matchups = self.top_matchups(3);
+        if !matchups.is_empty() {
+            summary.push_str("\nTop matchups:");
+            for matchup in matchups {
+                summary.push_str(&format!("\n  {matchup}"));
+            }
+        }
+        summary
+    }
+
+    /// The `count` most played character matchups in the games shown, like
+    /// "Fox vs Marth: 12-8 (60.0%)".
+    fn top_matchups(&self, count: usize) -> Vec<String> {
+        let code = self.me();
+        let mut matchups: HashMap<(u8, u8), (usize, usize)> = HashMap::new();
+        for (replay, &shown) in self.replay_analyzer.replays.iter().zip(&self.stats.shown) {
+            if !shown {
+                continue;
+            }
+            let (Some((me, opponent)), Some(won)) = (replay.perspective(code), replay.won_by(code))
+            else {
+                continue;
+            };
+            let (Some(mine), Some(theirs)) = (me.character, opponent.character) else {
+                continue;
+            };
+            let entry = matchups.entry((mine, theirs)).or_default();
+            if won {
+                entry.0 += 1;
+            } else {
+                entry.1 += 1;
+            }
+        }
+        let mut matchups: Vec<_> = matchups.into_iter().collect();
+        matchups.sort_by_key(|&(key, (w, l))| (std::cmp::Reverse(w + l), key));
+        matchups
+            .into_iter()
+            .take(count)
+            .map(|((mine, theirs), (w, l))| {
+                format!(
+                    "{} vs {}: {}",
+                    character_id_to_name(mine),
+                    character_id_to_name(theirs),
+                    record_text(w, l)
+                )
+            })
+            .collect()
+    }
+
+    /// Show the card being saved and, once it's been drawn, cut it out of a screenshot and
+    /// write it as a PNG.
+    fn stat_card_capture(&mut self, ctx: &egui::Context) {
+        let Some(capture) = &self.stat_card_capture else {
+            return;
+        };
+        let screenshot = ctx.input(|i| {
+            i.raw.events.iter().find_map(|event| match event {
+                egui::Event::Screenshot { image, .. } => Some(image.clone()),
+                _ => None,
+            })
+        });
+        if let (Some(screenshot), Some(rect)) = (screenshot, capture.rect) {
+            let path = capture.path.clone();
+            self.stat_card_capture = None;
+            let card = screenshot.region(&rect, Some(ctx.pixels_per_point()));
+            let result = image::save_buffer(
+                &path,
+                card.as_raw(),
+                card.width() as u32,
+                card.height() as u32,
+                image::ExtendedColorType::Rgba8,
+            );
+            self.scan_status = match result {
+                Ok(()) => format!("Saved stat card to {}", path.display()),
+                Err(e) => format!("Failed to save stat card: {e}"),
+            };
+            return;
+        }
+        if capture.started.elapsed() >= STAT_CARD_CAPTURE_TIMEOUT {
+            self.stat_card_capture = None;
+            self.scan_status =
+                "Failed to save stat card: the window screenshot never arrived".to_string();
+            return;
+        }
+
+        let card = StatCard {
+            connect_code: self.connect_code.clone(),
+            wins: self.stats.wins,
+            losses: self.stats.losses,
+            streak: self.stats.streak,
+            top_matchup: self.top_matchups(1).pop(),
+            rank: self
+                .replay_analyzer
+                .get_cached_rank(&self.connect_code)
+                .map(|rank| rank.label()),
+        };
+        let rank_icon = self
+            .replay_analyzer
+            .get_cached_rank(&self.connect_code)
+            .and_then(|rank| self.rank_icons.get(&rank.rank));
+        let rect = egui::Area::new(egui::Id::new("stat_card"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .fade_in(false)
+            .interactable(false)
+            .show(ctx, |ui| stat_card(ui, &card, rank_icon))
+            .inner
+            .rect;
+
+        let Some(capture) = &mut self.stat_card_capture else {
+            return;
+        };
+        // Areas are measured on the frame they first appear, so wait a frame before the screenshot
+        if capture.rect.is_some() && !capture.screenshot_requested {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot(egui::UserData::default()));
+            capture.screenshot_requested = true;
+        }
+        capture.rect = Some(rect);
+        ctx.request_repaint();
+    }
+
+    /// Whether `replay` passes the search (`query`, already lowercase) and the table filters.
+    fn shows_replay(&self, replay: &ReplayInfo, query: &str) -> bool {
+        let played_character = |character| {
+            replay
+                .perspective(self.me())
+                .is_some_and(|(me, _)| me.character == Some(character))
+        };
+        replay.matches_search(query)
+            && (!self.dittos_only || replay.is_ditto())
+            && (!self.ranked_only || replay.mode == GameMode::Ranked)
+            && (!self.legal_stages_only || is_tournament_legal(replay.stage))
+            && self.replay_analyzer.is_long_enough(replay)
+            && self.my_character.is_none_or(played_character)
+    }
+
     fn replays_table(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         // Always use striped rows, resizable columns and clickable rows.
         self.striped = true;
         self.resizable = true;
-        self.clickable = false;
+        self.clickable = true;
 
         // The demo modes have been removed ‑ we are always in replay-data mode.
         self.demo = DemoType::ReplayData;
+        self.debounce_search(ctx);
+        self.refresh_stats();
 
         ui.vertical(|ui| {
+            // A mistyped connect code otherwise just reads as 0-0
+            if !self.me().is_empty() && !self.stats.tag_found {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "⚠ Your connect code wasn't found in any replay",
+                    );
+                    if let Some(tag) = self.stats.suggested_tag.clone() {
+                        ui.label("— did you mean");
+                        if ui
+                            .link(&tag)
+                            .on_hover_text("Use this connect code")
+                            .clicked()
+                        {
+                            self.connect_code = tag;
+                            self.connect_code_changed();
+                        }
+                        ui.label("?");
+                    }
+                });
+            }
+
             // Display W/L stats if a connect code is provided
             ui.horizontal(|ui| {
-                if !self.connect_code.is_empty() {
-                    let (wins, losses) = self
-                        .replay_analyzer
-                        .get_stats_for_player(&self.connect_code);
-                    let total = wins + losses;
+                if !self.me().is_empty() {
+                    let PlayerStats {
+                        wins,
+                        losses,
+                        draws,
+                        streak,
+                        longest_streak: longest,
+                        ..
+                    } = self.stats;
+                    let mut total = wins + losses;
+                    if self.count_draws_in_win_rate {
+                        total += draws;
+                    }
                     let win_rate = if total > 0 {
                         wins as f64 / total as f64 * 100.0
                     } else {
                         0.0
                     };
                     ui.label(format!("W/L: {wins}/{losses} ({win_rate:.1}%)"));
+                    win_loss_chart(ui, "win_loss", wins, losses, egui::vec2(180.0, 56.0));
+
+                    let (games_today, wins_today, losses_today) = self
+                        .replay_analyzer
+                        .stats_since(self.me(), start_of_today());
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Today: {games_today} games, {wins_today}-{losses_today}"
+                        ))
+                        .strong(),
+                    )
+                    .on_hover_text("Games played since midnight");
+
+                    let (streak_text, streak_color) = match streak {
+                        s if s > 0 => (format!("W{s}"), egui::Color32::GREEN),
+                        s if s < 0 => (format!("L{}", -s), egui::Color32::RED),
+                        _ => ("-".to_string(), egui::Color32::GRAY),
+                    };
+                    ui.label("Streak:");
+                    ui.colored_label(streak_color, streak_text);
+                    ui.label(format!("Best: W{longest}"));
+
+                    if draws > 0 {
+                        ui.label(format!("Draws/NC: {draws}"));
+                        ui.checkbox(&mut self.count_draws_in_win_rate, "Count in win rate")
+                            .on_hover_text("Treat draws and no-contests as non-wins");
+                    }
+
+                    if ui
+                        .button("Copy Stats")
+                        .on_hover_text(
+                            "Copy your record, streak and top matchups for the games shown",
+                        )
+                        .clicked()
+                    {
+                        ui.ctx().copy_text(self.stats_summary());
+                        self.show_toast("Copied stats to the clipboard");
+                    }
+
+                    let can_lookup = !self.is_fetching_rank
+                        && !self.offline_mode
+                        && !self.replay_analyzer.replays.is_empty();
+                    if ui
+                        .add_enabled(can_lookup, egui::Button::new("Lookup All Ranks"))
+                        .on_hover_text("Fetch the rank of every opponent that isn't cached")
+                        .clicked()
+                    {
+                        self.lookup_all_ranks(ctx);
+                    }
+                }
+
+                if !self.replay_analyzer.replays.is_empty() {
+                    ui.label(format!(
+                        "Playtime: {}",
+                        format_playtime(self.stats.playtime_frames)
+                    ))
+                    .on_hover_text("Total length of the games shown, by the in-game timer");
                 }
             });
         });
 
         ui.separator();
 
+        self.search_bar(ui);
+        // The search bar's filters may have just changed
+        self.refresh_stats();
+
+        // Only the current page is handed to the table
+        let mut rows = self.table_rows();
+        self.pagination_bar(ui, rows.len());
+        if let Some(page_size) = self.page_size.rows() {
+            let start = self.page * page_size;
+            rows.truncate(start + page_size);
+            rows.drain(..start.min(rows.len()));
+        }
+
+        self.table_keyboard(ui.ctx(), &rows);
+
         // The table itself
         egui::ScrollArea::horizontal().show(ui, |ui| {
-            self.table_ui(ui, ctx, /*reset=*/ false);
+            self.table_ui(ui, ctx, rows, /*reset=*/ false);
+        });
+    }
+
+    /// Up/Down move the focused row, Space/Enter toggle its selection, and Shift+Up/Down select
+    /// the range from where Shift was first held. `rows` is the current page.
+    fn table_keyboard(&mut self, ctx: &egui::Context, rows: &[TableRow]) {
+        // Leave the keys to the search box or whatever else has keyboard focus
+        if ctx.memory(|m| m.focused().is_some()) {
+            return;
+        }
+        let replay_rows: Vec<usize> = rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| matches!(row, TableRow::Replay(_)))
+            .map(|(position, _)| position)
+            .collect();
+        let (Some(&first), Some(&last)) = (replay_rows.first(), replay_rows.last()) else {
+            return;
+        };
+        let (up, down, toggle, shift) = ctx.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+                i.key_pressed(egui::Key::Space) || i.key_pressed(egui::Key::Enter),
+                i.modifiers.shift,
+            )
+        });
+        // The focused replay may be on another page or filtered out
+        let focused = self
+            .focused_replay
+            .as_deref()
+            .and_then(|path| self.row_position(rows, path));
+        let current = focused.and_then(|row| replay_rows.iter().position(|&r| r == row));
+
+        if up != down {
+            let next = match current {
+                Some(i) if up => replay_rows[i.saturating_sub(1)],
+                Some(i) => replay_rows[(i + 1).min(replay_rows.len() - 1)],
+                None if up => last,
+                None => first,
+            };
+            if shift {
+                let anchor = self
+                    .selection_anchor
+                    .as_deref()
+                    .and_then(|path| self.row_position(rows, path))
+                    .unwrap_or(focused.unwrap_or(next));
+                if let Some(previous) = focused {
+                    for path in self.row_range_paths(rows, anchor, previous) {
+                        self.selection.remove(&path);
+                    }
+                }
+                self.selection
+                    .extend(self.row_range_paths(rows, anchor, next));
+                self.selection_anchor = self.row_path(rows, anchor);
+            } else {
+                self.selection_anchor = None;
+            }
+            self.focused_replay = self.row_path(rows, next);
+            self.scroll_to_row = Some(next);
+        }
+
+        if toggle && focused.is_some() {
+            if let Some(path) = self.focused_replay.clone() {
+                if !self.selection.remove(&path) {
+                    self.selection.insert(path);
+                }
+            }
+        }
+    }
+
+    /// Click selects only that row, Ctrl/Cmd+click toggles it, and Shift+click selects the range
+    /// from the anchor (the last plain or Ctrl click).
+    fn click_row(&mut self, rows: &[TableRow], position: usize, modifiers: egui::Modifiers) {
+        let clicked = self.row_path(rows, position);
+        if modifiers.shift {
+            let anchor = [&self.selection_anchor, &self.focused_replay]
+                .into_iter()
+                .flatten()
+                .find_map(|path| self.row_position(rows, path))
+                .unwrap_or(position);
+            self.selection = self
+                .row_range_paths(rows, anchor, position)
+                .into_iter()
+                .collect();
+            self.selection_anchor = self.row_path(rows, anchor);
+        } else {
+            // Replays without a file (sample data) have nothing to key on
+            let paths = self.row_range_paths(rows, position, position);
+            if modifiers.command {
+                for path in paths {
+                    if !self.selection.remove(&path) {
+                        self.selection.insert(path);
+                    }
+                }
+            } else {
+                self.selection = paths.into_iter().collect();
+            }
+            self.selection_anchor = clicked.clone();
+        }
+        self.focused_replay = clicked;
+    }
+
+    /// Position in `rows` of the replay with file path `path`.
+    fn row_position(&self, rows: &[TableRow], path: &str) -> Option<usize> {
+        rows.iter().position(|row| {
+            matches!(row, TableRow::Replay(index)
+                if self.replay_analyzer.replays[*index].file_path == path)
+        })
+    }
+
+    /// File path of the replay at `position` in `rows`, unless it's a session header or a
+    /// replay without a file.
+    fn row_path(&self, rows: &[TableRow], position: usize) -> Option<String> {
+        self.row_range_paths(rows, position, position).pop()
+    }
+
+    /// File paths of the replays between two rows of the page, inclusive, in either order.
+    fn row_range_paths(&self, rows: &[TableRow], a: usize, b: usize) -> Vec<String> {
+        rows[a.min(b)..=a.max(b).min(rows.len() - 1)]
+            .iter()
+            .filter_map(|row| match row {
+                TableRow::Replay(index) => Some(&self.replay_analyzer.replays[*index].file_path),
+                TableRow::Session(..) => None,
+            })
+            .filter(|path| !path.is_empty())
+            .cloned()
+            .collect()
+    }
+
+    /// Page size picker and prev/next buttons. Keeps `page` in range as the row count changes.
+    fn pagination_bar(&mut self, ui: &mut egui::Ui, total_rows: usize) {
+        let Some(page_size) = self.page_size.rows() else {
+            self.page = 0;
+            ui.horizontal(|ui| self.page_size_picker(ui));
+            return;
+        };
+        let pages = total_rows.div_ceil(page_size).max(1);
+        self.page = self.page.min(pages - 1);
+
+        ui.horizontal(|ui| {
+            self.page_size_picker(ui);
+            if ui
+                .add_enabled(self.page > 0, egui::Button::new("◀ Prev"))
+                .clicked()
+            {
+                self.page -= 1;
+            }
+            ui.label(format!("Page {} of {pages}", self.page + 1));
+            if ui
+                .add_enabled(self.page + 1 < pages, egui::Button::new("Next ▶"))
+                .clicked()
+            {
+                self.page += 1;
+            }
+        });
+    }
+
+    fn page_size_picker(&mut self, ui: &mut egui::Ui) {
+        ui.label("Rows per page:");
+        let before = self.page_size;
+        egui::ComboBox::from_id_salt("page_size")
+            .selected_text(self.page_size.label())
+            .show_ui(ui, |ui| {
+                for page_size in PageSize::ALL {
+                    ui.selectable_value(&mut self.page_size, page_size, page_size.label());
+                }
+            });
+        if self.page_size != before {
+            self.page = 0;
+        }
+    }
+
+    /// The filtered and sorted rows of the table, or sessions with their games when grouping.
+    /// The order is cached in `stats` until the filters, replays or sort change.
+    fn table_rows(&mut self) -> Vec<TableRow> {
+        let order = match self.stats.order.take() {
+            Some(order)
+                if order.fits(self.group_by_session, self.sort_column, self.sort_ascending) =>
+            {
+                order
+            }
+            _ => {
+                let order = self.table_order();
+                let analyzer = &self.replay_analyzer;
+                self.stats.on_streak = match &order {
+                    TableOrder::Sorted { replays, .. } => {
+                        analyzer.win_streak_mask(self.me(), replays.iter().copied())
+                    }
+                    TableOrder::Sessions(sessions) => analyzer.win_streak_mask(
+                        self.me(),
+                        sessions.iter().flat_map(|(_, games)| games.iter().copied()),
+                    ),
+                };
+                order
+            }
+        };
+        let rows = match &order {
+            TableOrder::Sessions(sessions) => {
+                let mut rows = Vec::new();
+                for (key, games) in sessions {
+                    rows.push(TableRow::Session(key.clone(), games.clone()));
+                    if !self.collapsed_sessions.contains(key) {
+                        rows.extend(games.iter().copied().map(TableRow::Replay));
+                    }
+                }
+                rows
+            }
+            TableOrder::Sorted { replays, .. } => {
+                replays.iter().copied().map(TableRow::Replay).collect()
+            }
+        };
+        self.stats.order = Some(order);
+        rows
+    }
+
+    /// Sort or group the shown replays for [`Self::table_rows`].
+    fn table_order(&self) -> TableOrder {
+        let replays = &self.replay_analyzer.replays;
+        let shown = |i: &usize| self.stats.shown.get(*i).copied().unwrap_or(false);
+
+        if self.group_by_session {
+            // Sessions stay in date order, each with a header row
+            let sessions = self
+                .replay_analyzer
+                .group_into_sessions(DEFAULT_SESSION_GAP)
+                .into_iter()
+                .filter_map(|session| {
+                    let key = session_key(replays, &session);
+                    let matching: Vec<usize> = session.into_iter().filter(shown).collect();
+                    (!matching.is_empty()).then_some((key, matching))
+                })
+                .collect();
+            TableOrder::Sessions(sessions)
+        } else {
+            // Sort a view of the matching rows; row indices still refer to `replays`
+            let mut visible: Vec<usize> = (0..replays.len()).filter(shown).collect();
+            visible.sort_by(|&a, &b| {
+                // Opponents without a known tier stay at the bottom in both directions
+                if self.sort_column == SortColumn::OpponentRank {
+                    let unranked = |i: usize| opponent_rank_ordinal(&replays[i]).is_none();
+                    if unranked(a) != unranked(b) {
+                        return unranked(a).cmp(&unranked(b));
+                    }
+                }
+                let ordering = self
+                    .sort_column
+                    .compare(&replays[a], &replays[b], self.me());
+                if self.sort_ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            });
+            TableOrder::Sorted {
+                column: self.sort_column,
+                ascending: self.sort_ascending,
+                replays: visible,
+            }
+        }
+    }
+
+    /// Apply `search_query` to the table once typing has paused for [`SEARCH_DEBOUNCE`], so
+    /// huge libraries aren't refiltered on every keystroke. Changes made other than by typing,
+    /// like picking a suggestion, apply right away.
+    fn debounce_search(&mut self, ctx: &egui::Context) {
+        let query = self.search_query.trim().to_lowercase();
+        if query == self.last_query {
+            self.search_edited_at = None;
+            return;
+        }
+        if let Some(edited_at) = self.search_edited_at {
+            let waited = edited_at.elapsed();
+            if waited < SEARCH_DEBOUNCE {
+                ctx.request_repaint_after(SEARCH_DEBOUNCE - waited);
+                return;
+            }
+        }
+        self.last_query = query;
+        self.search_edited_at = None;
+        self.filters_changed();
+    }
+
+    /// Call after the search or any table filter changes. The table goes back to its first
+    /// page and the stats are marked out of date.
+    fn filters_changed(&mut self) {
+        self.page = 0;
+        self.stats_dirty = true;
+    }
+
+    /// Search box filtering the table by player tag, with autocomplete from known tags.
+    fn search_bar(&mut self, ui: &mut egui::Ui) {
+        /// Maximum number of autocomplete suggestions shown.
+        const MAX_SUGGESTIONS: usize = 8;
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.search_query)
+                    .hint_text("Player tag, stage or rank"),
+            );
+            if response.changed() {
+                self.search_edited_at = Some(Instant::now());
+            }
+            if !self.search_query.is_empty() && ui.small_button("✖").clicked() {
+                self.search_query.clear();
+            }
+
+            let popup_id = ui.make_persistent_id("search_suggestions");
+            let suggestions = self
+                .replay_analyzer
+                .suggest_tags(&self.search_query, MAX_SUGGESTIONS);
+            // Nothing to suggest if the query already is the only match
+            let useful = !(suggestions.len() == 1 && suggestions[0] == self.search_query);
+            if response.changed() && useful && !suggestions.is_empty() {
+                ui.memory_mut(|m| m.open_popup(popup_id));
+            }
+            if !useful || suggestions.is_empty() {
+                ui.memory_mut(|m| {
+                    if m.is_popup_open(popup_id) {
+                        m.close_popup();
+                    }
+                });
+            }
+
+            egui::popup_below_widget(
+                ui,
+                popup_id,
+                &response,
+                egui::PopupCloseBehavior::CloseOnClickOutside,
+                |ui| {
+                    for tag in suggestions {
+                        if ui.selectable_label(false, &tag).clicked() {
+                            self.search_query = tag;
+                            ui.memory_mut(|m| m.close_popup());
+                        }
+                    }
+                },
+            );
+
+            ui.checkbox(&mut self.group_by_session, "Group by session")
+                .on_hover_text("Split games into sessions separated by an hour or more");
+            if ui
+                .checkbox(&mut self.dittos_only, "Dittos only")
+                .on_hover_text("Only show games where both players picked the same character")
+                .changed()
+            {
+                self.filters_changed();
+            }
+            if ui
+                .checkbox(&mut self.ranked_only, "Ranked only")
+                .on_hover_text("Only show ranked matchmaking games (needs Slippi 3.14 or newer)")
+                .changed()
+            {
+                self.filters_changed();
+            }
+            if ui
+                .checkbox(&mut self.legal_stages_only, "Legal stages only")
+                .on_hover_text("Only show games on FD, Battlefield, Yoshi's Story, Dream Land, Fountain of Dreams and Pokémon Stadium")
+                .changed()
+            {
+                self.filters_changed();
+            }
+            if ui
+                .checkbox(
+                    &mut self.hide_short_games,
+                    format!("Hide games under {}s", self.min_game_seconds),
+                )
+                .on_hover_text(
+                    "Leave out handwarmers and accidental starts. Change the length in Settings",
+                )
+                .changed()
+            {
+                self.sync_short_game_filter();
+                self.filters_changed();
+            }
+            if !self.me().is_empty() {
+                self.character_filter(ui);
+            }
+            ui.checkbox(&mut self.absolute_dates, "Exact dates")
+                .on_hover_text(
+                    "Show the date and time of each game instead of how long ago it was",
+                );
         });
     }
 
-    fn table_ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, reset: bool) {
+    /// A date as relative text or an absolute timestamp, depending on the "Exact dates" toggle.
+    fn format_date(&self, date: std::time::SystemTime) -> String {
+        if self.absolute_dates {
+            format_timestamp(date, TimestampPrecision::Minutes)
+        } else {
+            format_date(date)
+        }
+    }
+
+    /// Dropdown limiting the table to games where you played one character. Only lists
+    /// characters you've played in the loaded replays.
+    fn character_filter(&mut self, ui: &mut egui::Ui) {
+        let before = self.my_character;
+        let selected = self
+            .my_character
+            .map_or_else(|| "All".to_string(), character_id_to_name);
+        ui.label("Playing as:");
+        egui::ComboBox::from_id_salt("my_character")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                // Only worked out while the dropdown is open
+                let mut characters: Vec<u8> = self
+                    .replay_analyzer
+                    .get_stats_by_character(self.me())
+                    .into_keys()
+                    .collect();
+                characters.sort_by_key(|&character| character_id_to_name(character));
+
+                ui.selectable_value(&mut self.my_character, None, "All");
+                for character in characters {
+                    ui.selectable_value(
+                        &mut self.my_character,
+                        Some(character),
+                        character_id_to_name(character),
+                    );
+                }
+            });
+        if self.my_character != before {
+            self.filters_changed();
+        }
+    }
+
+    /// A player's tag and character; opponents are clickable to open their detail window.
+    fn player_label(
+        ui: &mut egui::Ui,
+        player: &PlayerInfo,
+        character_icons: &HashMap<u8, TextureHandle>,
+        connect_code: &str,
+        opponent_clicked: &mut Option<String>,
+    ) {
+        let (swatch, response) =
+            ui.allocate_exact_size(egui::vec2(4.0, 16.0), egui::Sense::hover());
+        ui.painter()
+            .rect_filled(swatch, 1.0, port_color(player.port));
+        response.on_hover_text(format!("Port {}", player.port as u8 + 1));
+        let icon = player.character.and_then(|id| character_icons.get(&id));
+        if let Some(icon) = icon {
+            ui.add(egui::Image::from_texture(icon).max_size(egui::Vec2::new(20.0, 20.0)))
+                .on_hover_text(&player.character_name);
+        }
+        // Show the display name where there is one; the connect code is what identifies them
+        let name = &player.name;
+        let shown = player.display_name.as_ref().unwrap_or(name);
+        if connect_code.is_empty() || same_tag(name, connect_code) || name == "Unknown" {
+            let label = ui.label(shown);
+            if shown != name {
+                label.on_hover_text(name);
+            }
+        } else if ui
+            .link(shown)
+            .on_hover_text(format!("{name}\nShow your record against this opponent"))
+            .clicked()
+        {
+            *opponent_clicked = Some(name.clone());
+        }
+        // Fall back to the character's name when there's no icon for it
+        if player.character.is_some() && icon.is_none() {
+            ui.colored_label(egui::Color32::GRAY, &player.character_name);
+        }
+    }
+
+    /// One side of a game: the player in singles. In doubles, four tags in two cells are hard
+    /// to read, so it's the team and its characters, e.g. "Red Team (Fox/Falco)", with the
+    /// tags on hover. `team_number` (1 or 2) names teams without a color.
+    fn side_label(
+        ui: &mut egui::Ui,
+        side: &[&PlayerInfo],
+        team_number: usize,
+        character_icons: &HashMap<u8, TextureHandle>,
+        connect_code: &str,
+        opponent_clicked: &mut Option<String>,
+    ) {
+        if side.len() > 1 {
+            let team = side[0]
+                .team
+                .and_then(team_name)
+                .map_or_else(|| format!("Team {team_number}"), str::to_string);
+            let characters: Vec<&str> = side.iter().map(|p| p.character_name.as_str()).collect();
+            let tags: Vec<&str> = side.iter().map(|p| p.name.as_str()).collect();
+            ui.label(format!("{team} ({})", characters.join("/")))
+                .on_hover_text(tags.join(" & "));
+            return;
+        }
+        for (i, player) in side.iter().enumerate() {
+            if i > 0 {
+                ui.label("&");
+            }
+            Self::player_label(ui, player, character_icons, connect_code, opponent_clicked);
+        }
+    }
+
+    /// Record against the selected opponent and the characters they've played against us.
+    fn opponent_window(&mut self, ctx: &egui::Context) {
+        let Some(opponent) = self.selected_opponent.clone() else {
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new(format!("Opponent: {opponent}"))
+            .id(egui::Id::new("opponent_window"))
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let analyzer = &self.replay_analyzer;
+                let (wins, losses) = analyzer
+                    .head_to_head(self.me())
+                    .get(&opponent)
+                    .copied()
+                    .unwrap_or_default();
+                ui.label(format!("Your record: {wins}-{losses}"));
+                if let Some(rank) = analyzer.get_cached_rank(&opponent) {
+                    ui.label(format!("Rank: {}", rank.label()));
+                }
+
+                ui.separator();
+                ui.strong("Their characters against you");
+
+                let mut characters: Vec<_> = analyzer
+                    .opponent_character_stats(self.me(), &opponent)
+                    .into_iter()
+                    .collect();
+                characters
+                    .sort_by_key(|(character, (w, l))| (std::cmp::Reverse(w + l), *character));
+
+                if characters.is_empty() {
+                    ui.colored_label(egui::Color32::GRAY, "No decided games yet");
+                }
+                egui::Grid::new("opponent_characters")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (character, (their_wins, their_losses)) in characters {
+                            let games = their_wins + their_losses;
+                            ui.label(character_id_to_name(character));
+                            ui.label(format!("{games} games"));
+                            ui.label(format!(
+                                "{their_wins}-{their_losses} ({:.1}%)",
+                                their_wins as f64 / games as f64 * 100.0
+                            ));
+                            ui.end_row();
+                        }
+                    });
+            });
+        if !open {
+            self.selected_opponent = None;
+        }
+    }
+
+    /// Collapsible stat breakdowns for the connect code's games, below the table.
+    fn breakdown_panel(&mut self, ctx: &egui::Context) {
+        if self.me().is_empty() || self.replay_analyzer.replays.is_empty() {
+            return;
+        }
+
+        let mut head_to_head_sort = self.head_to_head_sort;
+        let mut filter_to_opponent = None;
+        egui::TopBottomPanel::bottom("breakdown_panel")
+            .resizable(true)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.checkbox(
+                        &mut self.replay_analyzer.legal_stages_only,
+                        "Legal stages only",
+                    )
+                    .on_hover_text(
+                        "Only count games on tournament-legal stages in these breakdowns",
+                    );
+                    let analyzer = &self.replay_analyzer;
+                    let code = PlayerTag::new(&self.connect_code, &self.name_tag);
+
+                    ui.collapsing("By character", |ui| {
+                        let mut characters: Vec<_> =
+                            analyzer.get_stats_by_character(code).into_iter().collect();
+                        characters.sort_by_key(|(character, (w, l))| {
+                            (std::cmp::Reverse(w + l), *character)
+                        });
+                        if characters.is_empty() {
+                            ui.colored_label(egui::Color32::GRAY, "No decided games yet");
+                        }
+                        for (character, (wins, losses)) in characters {
+                            ui.label(format!(
+                                "{}: {}",
+                                character_id_to_name(character),
+                                record_text(wins, losses)
+                            ));
+                        }
+                    });
+
+                    ui.collapsing("Win rate over time", |ui| {
+                        let sessions = analyzer.session_records(code, DEFAULT_SESSION_GAP);
+                        if sessions.is_empty() {
+                            ui.colored_label(egui::Color32::GRAY, "No decided games yet");
+                            return;
+                        }
+                        // Local days since the epoch, so whole x values fall on local midnights
+                        const DAY_SECS: f64 = 86_400.0;
+                        let points: Vec<(f64, f32)> = sessions
+                            .iter()
+                            .map(|&(date, wins, losses)| {
+                                let local = chrono::DateTime::<chrono::Local>::from(date);
+                                let x = local.naive_local().and_utc().timestamp() as f64 / DAY_SECS;
+                                (x, wins as f32 / (wins + losses) as f32 * 100.0)
+                            })
+                            .collect();
+                        let day_label = |days: f64| {
+                            chrono::DateTime::from_timestamp((days * DAY_SECS) as i64, 0)
+                                .map_or_else(String::new, |day| day.format("%Y-%m-%d").to_string())
+                        };
+                        ui.label(format!("Per play session, {} sessions", sessions.len()));
+                        if let Some(i) =
+                            percent_line_chart(ui, "session_win_rate", &points, 160.0, day_label)
+                        {
+                            let (date, wins, losses) = sessions[i];
+                            egui::show_tooltip_at_pointer(
+                                ui.ctx(),
+                                ui.layer_id(),
+                                egui::Id::new("session_win_rate_tooltip"),
+                                |ui| {
+                                    ui.label(format!(
+                                        "{}: {}",
+                                        format_timestamp(date, TimestampPrecision::Minutes),
+                                        record_text(wins, losses)
+                                    ));
+                                },
+                            );
+                        }
+                    });
+
+                    ui.collapsing("By stage", |ui| {
+                        // Fold unrecognized stage IDs into one row
+                        let mut by_name: HashMap<String, (usize, usize)> = HashMap::new();
+                        for (stage, (wins, losses)) in analyzer.get_stats_by_stage(code) {
+                            let mut name = stage_id_to_name(stage);
+                            if name.starts_with("Unknown Stage") {
+                                name = "Unknown Stage".to_string();
+                            }
+                            let entry = by_name.entry(name).or_default();
+                            entry.0 += wins;
+                            entry.1 += losses;
+                        }
+                        let mut stages: Vec<_> = by_name.into_iter().collect();
+                        stages.sort_by(|(a, (aw, al)), (b, (bw, bl))| {
+                            (bw + bl).cmp(&(aw + al)).then_with(|| a.cmp(b))
+                        });
+                        if stages.is_empty() {
+                            ui.colored_label(egui::Color32::GRAY, "No decided games yet");
+                        }
+                        egui::Grid::new("stage_breakdown")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Stage");
+                                ui.strong("Games");
+                                ui.strong("Record");
+                                ui.end_row();
+                                for (stage, (wins, losses)) in stages {
+                                    ui.label(stage);
+                                    ui.label((wins + losses).to_string());
+                                    ui.label(record_text(wins, losses));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                    ui.collapsing("Head-to-head", |ui| {
+                        let mut opponents: Vec<_> =
+                            analyzer.head_to_head(code).into_iter().collect();
+                        let (column, ascending) = head_to_head_sort;
+                        opponents.sort_by(|(a, (aw, al)), (b, (bw, bl))| {
+                            let ordering = match column {
+                                HeadToHeadSort::Opponent => a.to_lowercase().cmp(&b.to_lowercase()),
+                                HeadToHeadSort::Games => (aw + al).cmp(&(bw + bl)),
+                                HeadToHeadSort::WinRate => {
+                                    // Cross-multiplied to compare aw/(aw+al) with bw/(bw+bl)
+                                    (aw * (bw + bl)).cmp(&(bw * (aw + al)))
+                                }
+                            };
+                            let ordering = if ascending {
+                                ordering
+                            } else {
+                                ordering.reverse()
+                            };
+                            ordering.then_with(|| a.cmp(b))
+                        });
+                        if opponents.is_empty() {
+                            ui.colored_label(egui::Color32::GRAY, "No decided games yet");
+                        }
+                        egui::Grid::new("head_to_head")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (label, header) in [
+                                    ("Opponent", HeadToHeadSort::Opponent),
+                                    ("Games", HeadToHeadSort::Games),
+                                    ("Record", HeadToHeadSort::WinRate),
+                                ] {
+                                    let text = if column == header {
+                                        format!("{label} {}", if ascending { "⬆" } else { "⬇" })
+                                    } else {
+                                        label.to_string()
+                                    };
+                                    if ui
+                                        .add(
+                                            egui::Button::new(egui::RichText::new(text).strong())
+                                                .frame(false),
+                                        )
+                                        .clicked()
+                                    {
+                                        head_to_head_sort = if column == header {
+                                            (header, !ascending)
+                                        } else {
+                                            (header, header == HeadToHeadSort::Opponent)
+                                        };
+                                    }
+                                }
+                                ui.end_row();
+                                for (opponent, (wins, losses)) in opponents {
+                                    if ui
+                                        .link(&opponent)
+                                        .on_hover_text("Show only games against this opponent")
+                                        .clicked()
+                                    {
+                                        filter_to_opponent = Some(opponent.clone());
+                                    }
+                                    ui.label((wins + losses).to_string());
+                                    ui.label(record_text(wins, losses));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                    ui.collapsing("Matchups", |ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.matchup_filter)
+                                .hint_text("Filter by character"),
+                        );
+                        let filter = self.matchup_filter.trim().to_lowercase();
+                        let mut matchups: Vec<_> = analyzer
+                            .matchup_stats(code)
+                            .into_iter()
+                            .map(|((mine, theirs), record)| {
+                                let name = format!(
+                                    "{} vs {}",
+                                    character_id_to_name(mine),
+                                    character_id_to_name(theirs)
+                                );
+                                (name, record)
+                            })
+                            .filter(|(name, _)| name.to_lowercase().contains(&filter))
+                            .collect();
+                        matchups.sort_by(|(a, (aw, al)), (b, (bw, bl))| {
+                            (bw + bl).cmp(&(aw + al)).then_with(|| a.cmp(b))
+                        });
+                        if matchups.is_empty() {
+                            ui.colored_label(egui::Color32::GRAY, "No matching games");
+                        }
+                        for (name, (wins, losses)) in matchups {
+                            ui.label(format!("{name}: {}", record_text(wins, losses)));
+                        }
+                    });
+                });
+            });
+        self.head_to_head_sort = head_to_head_sort;
+        if let Some(opponent) = filter_to_opponent {
+            self.search_query = opponent;
+        }
+    }
+
+    /// Clickable column header: sorts by `column`, or flips the direction if it already does.
+    fn sort_header(&mut self, ui: &mut egui::Ui, label: &str, column: SortColumn) {
+        let text = if self.sort_column == column {
+            let arrow = if self.sort_ascending { "⬆" } else { "⬇" };
+            format!("{label} {arrow}")
+        } else {
+            label.to_string()
+        };
+        if ui
+            .add(egui::Button::new(egui::RichText::new(text).strong()).frame(false))
+            .clicked()
+        {
+            if self.sort_column == column {
+                self.sort_ascending = !self.sort_ascending;
+            } else {
+                self.sort_column = column;
+                self.sort_ascending = true;
+            }
+        }
+    }
+
+    fn table_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        ctx: &egui::Context,
+        rows: Vec<TableRow>,
+        reset: bool,
+    ) {
         use egui_extras::{Column, TableBuilder};
 
         let text_height = egui::TextStyle::Body
@@ -468,8 +3347,8 @@ impl Eppi {
             .striped(self.striped)
             .resizable(self.resizable)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .column(Column::auto().at_least(100.0)) // Player 1
-            .column(Column::auto().at_least(100.0)) // Player 2
+            .column(Column::auto().at_least(160.0)) // Player 1
+            .column(Column::auto().at_least(160.0)) // Player 2
             .column(Column::auto().at_least(60.0)) // Result
             .column(Column::auto().at_least(120.0)) // Stage
             .column(Column::auto().at_least(80.0)) // Date
@@ -493,32 +3372,37 @@ impl Eppi {
         table
             .header(20.0, |mut header| {
                 header.col(|ui| {
-                    ui.strong("Player 1");
+                    self.sort_header(ui, "Player 1", SortColumn::Player1);
                 });
                 header.col(|ui| {
-                    ui.strong("Player 2");
+                    self.sort_header(ui, "Player 2", SortColumn::Player2);
                 });
                 header.col(|ui| {
-                    ui.strong("Result");
+                    self.sort_header(ui, "Result", SortColumn::Result);
                 });
                 header.col(|ui| {
-                    ui.strong("Stage");
+                    self.sort_header(ui, "Stage", SortColumn::Stage);
                 });
                 header.col(|ui| {
-                    ui.strong("Date");
+                    self.sort_header(ui, "Date", SortColumn::Date);
                 });
                 header.col(|ui| {
-                    ui.strong("Duration");
+                    self.sort_header(ui, "Duration", SortColumn::Duration);
                 });
                 header.col(|ui| {
-                    ui.strong("Opponent Rank");
+                    self.sort_header(ui, "Opponent Rank", SortColumn::OpponentRank);
                 });
             })
             .body(|mut body| {
                 let replays = &self.replay_analyzer.replays;
-                let connect_code = &self.connect_code;
-                let mut rows_to_toggle = Vec::new();
+                let me = PlayerTag::new(&self.connect_code, &self.name_tag);
+                let on_streak = &self.stats.on_streak;
+                let mut replay_to_open = None;
+                let mut replay_to_reveal = None;
+                let mut replay_to_delete = None;
+                let mut copy_result = None;
                 let mut ranks_to_fetch = Vec::new();
+                let mut opponent_clicked = None;
 
                 if replays.is_empty() {
                     // Show helpful message when no replays are loaded
@@ -547,58 +3431,140 @@ impl Eppi {
                     });
                 }
 
-                for (row_index, replay) in replays.iter().enumerate() {
+                let mut sessions_to_toggle = Vec::new();
+                let mut row_clicked = None;
+                for (position, row) in rows.iter().enumerate() {
+                    let row_index = match row {
+                        TableRow::Session(key, games) => {
+                            let collapsed = self.collapsed_sessions.contains(key);
+                            let (wins, losses) = games.iter().fold((0, 0), |(w, l), &i| {
+                                match replays[i].won_by(me) {
+                                    Some(true) => (w + 1, l),
+                                    Some(false) => (w, l + 1),
+                                    None => (w, l),
+                                }
+                            });
+                            body.row(text_height, |mut row| {
+                                row.col(|ui| {
+                                    let arrow = if collapsed { "⏵" } else { "⏷" };
+                                    let date = replays[games[0]]
+                                        .date
+                                        .map_or_else(|| "Unknown date".to_string(), |date| self.format_date(date));
+                                    let header = egui::RichText::new(format!("{arrow} {date}")).strong();
+                                    if ui.add(egui::Button::new(header).frame(false)).clicked() {
+                                        sessions_to_toggle.push(key.clone());
+                                    }
+                                });
+                                row.col(|ui| {
+                                    ui.label(format!("{} games", games.len()));
+                                });
+                                row.col(|ui| {
+                                    if !me.is_empty() {
+                                        ui.label(format!("{wins}-{losses}"));
+                                    }
+                                });
+                                for _ in 0..4 {
+                                    row.col(|_| {});
+                                }
+                            });
+                            continue;
+                        }
+                        TableRow::Replay(row_index) => *row_index,
+                    };
+                    let replay = &replays[row_index];
+                    let sides = replay.sides();
                     body.row(text_height, |mut row| {
-                        row.set_selected(self.selection.contains(&row_index));
+                        row.set_selected(self.selection.contains(&replay.file_path));
+                        if self.focused_replay.as_ref() == Some(&replay.file_path) {
+                            row.set_hovered(true);
+                        }
 
                         row.col(|ui| {
-                            ui.label(&replay.player1.name);
+                            // Colored left border groups consecutive wins into a visible run
+                            if on_streak[row_index] {
+                                let rect = ui.max_rect();
+                                let border = egui::Rect::from_min_size(
+                                    rect.left_top() - egui::vec2(4.0, 0.0),
+                                    egui::vec2(3.0, rect.height()),
+                                );
+                                ui.painter()
+                                    .rect_filled(border, 0.0, egui::Color32::from_rgb(60, 180, 75));
+                            }
+                            Self::side_label(
+                                ui,
+                                &sides[0],
+                                1,
+                                &self.character_icons,
+                                me.connect_code,
+                                &mut opponent_clicked,
+                            );
                         });
                         row.col(|ui| {
-                            ui.label(&replay.player2.name);
+                            Self::side_label(
+                                ui,
+                                &sides[1],
+                                2,
+                                &self.character_icons,
+                                me.connect_code,
+                                &mut opponent_clicked,
+                            );
                         });
                         row.col(|ui| {
-                            let (result_text, color) = match &replay.result {
-                                GameResult::Player1Won => {
-                                    if !connect_code.is_empty()
-                                        && replay.player1.name == *connect_code
-                                    {
-                                        ("WIN", egui::Color32::GREEN)
-                                    } else if !connect_code.is_empty()
-                                        && replay.player2.name == *connect_code
-                                    {
-                                        ("LOSS", egui::Color32::RED)
-                                    } else {
-                                        ("P1 Win", egui::Color32::GRAY)
-                                    }
+                            let my_result = if me.is_empty() {
+                                None
+                            } else {
+                                replay.won_by(me)
+                            };
+                            let (result_text, color) = match (my_result, &replay.result) {
+                                (Some(true), _) => ("WIN", egui::Color32::GREEN),
+                                (Some(false), _) => ("LOSS", egui::Color32::RED),
+                                (None, GameResult::Player1Won) if replay.is_teams() => {
+                                    ("Team 1 Win", egui::Color32::GRAY)
                                 }
-                                GameResult::Player2Won => {
-                                    if !connect_code.is_empty()
-                                        && replay.player2.name == *connect_code
-                                    {
-                                        ("WIN", egui::Color32::GREEN)
-                                    } else if !connect_code.is_empty()
-                                        && replay.player1.name == *connect_code
-                                    {
-                                        ("LOSS", egui::Color32::RED)
-                                    } else {
-                                        ("P2 Win", egui::Color32::GRAY)
-                                    }
+                                (None, GameResult::Player2Won) if replay.is_teams() => {
+                                    ("Team 2 Win", egui::Color32::GRAY)
                                 }
-                                GameResult::Unknown => ("Unknown", egui::Color32::YELLOW),
+                                (None, GameResult::Player1Won) => ("P1 Win", egui::Color32::GRAY),
+                                (None, GameResult::Player2Won) => ("P2 Win", egui::Color32::GRAY),
+                                (None, GameResult::Draw) => ("DRAW", egui::Color32::YELLOW),
+                                (None, GameResult::NoContest) => ("No Contest", egui::Color32::GRAY),
+                                (None, GameResult::Unknown) => ("Unknown", egui::Color32::YELLOW),
                             };
-                            ui.colored_label(color, result_text);
+                            let label = ui.colored_label(color, result_text);
+                            if let Some(stocks) = replay.stock_differential() {
+                                label.on_hover_text(format!("Stocks: {stocks}"));
+                            }
+                            match replay.end_method {
+                                GameEndMethod::Timeout => {
+                                    ui.label("⏱").on_hover_text("Ended on time");
+                                }
+                                GameEndMethod::Lras => {
+                                    ui.label("🚪").on_hover_text("Someone quit out (LRAS)");
+                                }
+                                GameEndMethod::Stocks | GameEndMethod::Unknown => {}
+                            }
                         });
                         row.col(|ui| {
+                            if let Some(icon) = self.stage_icons.get(&replay.stage) {
+                                ui.add(egui::Image::from_texture(icon).max_size(egui::Vec2::new(32.0, 20.0)));
+                            }
                             ui.label(&replay.stage_name);
                         });
                         row.col(|ui| {
-                            let date_text = if let Some(date) = replay.date {
-                                format_date(date)
-                            } else {
-                                "Unknown".to_string()
-                            };
-                            ui.label(date_text);
+                            match replay.date {
+                                Some(date) if self.absolute_dates && replay.date_is_approximate() => {
+                                    ui.label(format!("{}*", self.format_date(date))).on_hover_text(
+                                        "Approximate: this replay has no recorded start time, so this is \
+                                         when the file was last modified",
+                                    );
+                                }
+                                Some(date) => {
+                                    ui.label(self.format_date(date));
+                                }
+                                None => {
+                                    ui.label("Unknown");
+                                }
+                            }
                         });
                         row.col(|ui| {
                             let duration_text = if let Some(duration_frames) = replay.duration {
@@ -610,33 +3576,40 @@ impl Eppi {
                         });
                         row.col(|ui| {
                             // Show opponent rank based on who the user is
-                            let opponent_name = if !connect_code.is_empty() {
-                                if replay.player1.name == *connect_code {
-                                    Some(&replay.player2.name)
-                                } else if replay.player2.name == *connect_code {
-                                    Some(&replay.player1.name)
-                                } else {
-                                    None
-                                }
+                            let opponent_name = if !me.is_empty() {
+                                replay
+                                    .perspective(me)
+                                    .map(|(_, opponent)| &opponent.name)
                             } else {
                                 None
                             };
 
                             if let Some(opponent_name) = opponent_name {
                                 // Check if we have this opponent's rank cached
-                                if let Some(cached_rank) = self.replay_analyzer.get_cached_rank(opponent_name) {
+                                if let Some(cached_rank) = self.replay_analyzer.get_fresh_rank(opponent_name) {
                                     // Display icon and rank text horizontally
                                     ui.horizontal(|ui| {
                                         // Show rank icon if available
-                                        if let Some(icon_texture) = self.rank_icons.get(cached_rank) {
+                                        if let Some(icon_texture) = self.rank_icons.get(&cached_rank.rank) {
                                             ui.add(egui::Image::from_texture(icon_texture).max_size(egui::Vec2::new(20.0, 20.0)));
                                         }
-                                        ui.label(cached_rank);
+                                        ui.label(cached_rank.label())
+                                            .on_hover_text(cached_rank.summary());
                                     });
                                 } else {
-                                    // Show fetch rank button if rank not cached
-                                    ui.add_enabled_ui(!self.is_fetching_rank, |ui| {
-                                        if ui.small_button("Fetch Rank").clicked() {
+                                    // Rank not looked up yet: show a "?" badge that fetches it on click,
+                                    // so it reads differently from a fetched "Unranked"
+                                    ui.add_enabled_ui(!self.is_fetching_rank && !self.offline_mode, |ui| {
+                                        let badge = egui::Button::new(
+                                            egui::RichText::new("?").color(egui::Color32::GRAY),
+                                        )
+                                        .small()
+                                        .corner_radius(8.0);
+                                        if ui
+                                            .add(badge)
+                                            .on_hover_text("Rank not looked up yet. Click to fetch.")
+                                            .clicked()
+                                        {
                                             ranks_to_fetch.push(opponent_name.clone());
                                         }
                                     });
@@ -646,36 +3619,272 @@ impl Eppi {
                             }
                         });
 
+                        // Only build the text for the row actually hovered
+                        row.response().on_hover_ui(|ui| {
+                            ui.label(replay_tooltip(replay));
+                        });
                         if row.response().clicked() {
-                            rows_to_toggle.push(row_index);
+                            row_clicked = Some((position, ctx.input(|i| i.modifiers)));
+                        }
+                        if row.response().double_clicked() {
+                            replay_to_open = Some(replay.file_path.clone());
                         }
+                        let has_file = !replay.file_path.is_empty();
+                        row.response().context_menu(|ui| {
+                            if ui
+                                .add_enabled(has_file, egui::Button::new("Reveal in file explorer"))
+                                .clicked()
+                            {
+                                replay_to_reveal = Some(replay.file_path.clone());
+                                ui.close_menu();
+                            }
+                            if ui
+                                .add_enabled(has_file, egui::Button::new("Copy file path"))
+                                .clicked()
+                            {
+                                ui.ctx().copy_text(replay.file_path.clone());
+                                ui.close_menu();
+                            }
+                            if ui.button("Copy opponent connect code").clicked() {
+                                // Only games you played in have an opponent; netplay without
+                                // a code records "Unknown"
+                                copy_result = Some(match replay.perspective(me) {
+                                    Some((_, opponent)) if opponent.name != "Unknown" => {
+                                        ui.ctx().copy_text(opponent.name.clone());
+                                        Ok(opponent.name.clone())
+                                    }
+                                    Some(_) => Err("This opponent has no connect code"),
+                                    None => Err("Your connect code isn't in this game"),
+                                });
+                                ui.close_menu();
+                            }
+                            ui.separator();
+                            if ui
+                                .add_enabled(has_file, egui::Button::new("Delete replay…"))
+                                .clicked()
+                            {
+                                replay_to_delete = Some(replay.file_path.clone());
+                                ui.close_menu();
+                            }
+                        });
                     });
                 }
 
-                // Handle row selection after the iteration
-                for row_index in rows_to_toggle {
-                    if self.selection.contains(&row_index) {
-                        self.selection.remove(&row_index);
-                    } else {
-                        self.selection.insert(row_index);
+                for key in sessions_to_toggle {
+                    if !self.collapsed_sessions.remove(&key) {
+                        self.collapsed_sessions.insert(key);
                     }
                 }
 
+                // Handle row selection after the iteration
+                if let Some((position, modifiers)) = row_clicked {
+                    self.click_row(&rows, position, modifiers);
+                }
+
+                if let Some(file_path) = replay_to_open {
+                    self.open_replay(&file_path);
+                }
+                if let Some(file_path) = replay_to_reveal {
+                    self.reveal_replay(&file_path);
+                }
+                if replay_to_delete.is_some() {
+                    self.pending_delete = replay_to_delete;
+                }
+                match copy_result {
+                    Some(Ok(code)) => self.show_toast(format!("Copied {code}")),
+                    Some(Err(reason)) => self.show_toast(reason),
+                    None => {}
+                }
+
                 // Handle rank fetching after the iteration
-                for opponent_name in ranks_to_fetch {
+                if opponent_clicked.is_some() {
+                    self.selected_opponent = opponent_clicked;
+                }
+
+                // Only fetch one rank at a time to avoid overwhelming the API
+                if let Some(opponent_name) = ranks_to_fetch.into_iter().next() {
                     self.lookup_opponent_rank(ctx, opponent_name);
-                    break; // Only fetch one rank at a time to avoid overwhelming the API
                 }
             });
     }
 
-    // fn toggle_row_selection(&mut self, row_index: usize, row_response: &egui::Response) {
-    //     if row_response.clicked() {
-    //         if self.selection.contains(&row_index) {
-    //             self.selection.remove(&row_index);
-    //         } else {
-    //             self.selection.insert(row_index);
-    //         }
+    // fn toggle_row_selection(&mut self, file_path: &str, row_response: &egui::Response) {
+    //     if row_response.clicked() && !self.selection.remove(file_path) {
+    //         self.selection.insert(file_path.to_string());
     //     }
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Storage holding just the app state.
+    struct StoredState(String);
+
+    impl eframe::Storage for StoredState {
+        fn get_string(&self, key: &str) -> Option<String> {
+            (key == eframe::APP_KEY).then(|| self.0.clone())
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            if key == eframe::APP_KEY {
+                self.0 = value;
+            }
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn lenient_restore_resets_corrupt_fields_to_app_defaults() {
+        let storage = StoredState(
+            r#"(connect_code: "BEAN#888", min_game_seconds: "long", rank_cache_ttl_hours: -1, striped: 3)"#
+                .to_string(),
+        );
+        assert!(eframe::get_value::<Eppi>(&storage, eframe::APP_KEY).is_none());
+
+        let app = Eppi::restore_leniently(&storage).expect("state should be recovered");
+        let defaults = Eppi::default();
+        assert_eq!(app.connect_code, "BEAN#888");
+        assert_eq!(app.min_game_seconds, defaults.min_game_seconds);
+        assert_eq!(app.rank_cache_ttl_hours, defaults.rank_cache_ttl_hours);
+        assert_eq!(app.striped, defaults.striped);
+    }
+
+    /// The field names of a stored struct, whatever their values.
+    struct StoredFields(Vec<String>);
+
+    /// A struct field name. RON writes them as bare identifiers, not strings.
+    struct FieldName(String);
+
+    impl<'de> serde::Deserialize<'de> for FieldName {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct Visitor;
+
+            impl serde::de::Visitor<'_> for Visitor {
+                type Value = FieldName;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str("a field name")
+                }
+
+                fn visit_str<E>(self, name: &str) -> Result<FieldName, E> {
+                    Ok(FieldName(name.to_string()))
+                }
+            }
+
+            deserializer.deserialize_identifier(Visitor)
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for StoredFields {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct Visitor;
+
+            impl<'de> serde::de::Visitor<'de> for Visitor {
+                type Value = StoredFields;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str("a struct")
+                }
+
+                fn visit_map<A: serde::de::MapAccess<'de>>(
+                    self,
+                    mut map: A,
+                ) -> Result<StoredFields, A::Error> {
+                    let mut fields = Vec::new();
+                    while let Some((FieldName(field), _)) =
+                        map.next_entry::<FieldName, serde::de::IgnoredAny>()?
+                    {
+                        fields.push(field);
+                    }
+                    Ok(StoredFields(fields))
+                }
+            }
+
+            deserializer.deserialize_any(Visitor)
+        }
+    }
+
+    #[test]
+    fn every_persisted_field_is_restored_leniently() {
+        let mut storage = StoredState(String::new());
+        eframe::set_value(&mut storage, eframe::APP_KEY, &Eppi::default());
+        let StoredFields(fields) =
+            eframe::get_value(&storage, eframe::APP_KEY).expect("state should be stored");
+
+        assert!(fields.contains(&"connect_code".to_string()));
+        for field in fields {
+            assert!(
+                LenientState::FIELDS.contains(&field.as_str()),
+                "`{field}` is persisted but not listed in `lenient_state!`"
+            );
+        }
+    }
+
+    /// Knows one player's rank; everyone else isn't found.
+    struct StubRankProvider;
+
+    impl RankProvider for StubRankProvider {
+        fn fetch<'a>(
+            &'a self,
+            player_tag: &'a str,
+        ) -> futures_util::future::BoxFuture<'a, Result<RankInfo, crate::web::RankLookupError>>
+        {
+            Box::pin(async move {
+                match player_tag {
+                    "FOXY#123" => Ok(RankInfo::unrated("Master 3")),
+                    _ => Err(crate::web::RankLookupError::NotFound),
+                }
+            })
+        }
+    }
+
+    /// Wait for the rank lookup in flight and apply its result, as `update` would.
+    async fn finish_rank_lookup(app: &mut Eppi) {
+        let receiver = app
+            .rank_receiver
+            .take()
+            .expect("a lookup should be running");
+        let (tag, result) = tokio::task::spawn_blocking(move || receiver.recv().unwrap())
+            .await
+            .unwrap();
+        app.apply_rank_result(tag, result);
+        app.is_fetching_rank = false;
+    }
+
+    #[tokio::test]
+    async fn rank_lookups_use_the_rank_provider() {
+        let ctx = egui::Context::default();
+        let mut app = Eppi::default();
+        app.replay_analyzer.load_sample_data();
+        app.connect_code = SAMPLE_CONNECT_CODE.to_string();
+        app.set_rank_provider(Arc::new(StubRankProvider));
+
+        // Cached ranks don't need the provider
+        app.lookup_opponent_rank(&ctx, "FOXY#123".to_string());
+        assert!(app.rank_receiver.is_none());
+
+        app.fetch_rank(&ctx, "FOXY#123".to_string());
+        finish_rank_lookup(&mut app).await;
+        let foxy_ranks: Vec<_> = app
+            .replay_analyzer
+            .replays
+            .iter()
+            .filter(|replay| {
+                replay
+                    .perspective(&app.connect_code)
+                    .is_some_and(|(_, opponent)| opponent.name == "FOXY#123")
+            })
+            .map(|replay| replay.opponent_rank.as_ref().map(|rank| rank.rank.as_str()))
+            .collect();
+        assert_eq!(foxy_ranks, [Some("Master 3"), Some("Master 3")]);
+
+        // Uncached opponents are looked up, and failures cached
+        app.lookup_opponent_rank(&ctx, "NEWB#001".to_string());
+        finish_rank_lookup(&mut app).await;
+        let cached = app.replay_analyzer.get_cached_rank("NEWB#001");
+        assert_eq!(cached.map(|rank| rank.rank.as_str()), Some("Unknown"));
+    }
+}
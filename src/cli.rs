@@ -0,0 +1,258 @@
+//! Headless command-line mode, for scripting without launching the GUI.
+
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::atomic::AtomicBool;
+
+use crate::peppi::{
+    character_id_to_name, iso_8601, normalize_tag, parse_replay_from_reader, stage_id_to_name,
+    GameResult, PlayerInfo, ReplayAnalyzer, ReplayInfo, ScanProgress,
+};
+
+const USAGE: &str = "Usage: eppi [--stdin]
+       eppi --scan <dir> [--scan <dir>...] [--tag <code>] [--json | --dump]
+
+  --stdin   Parse a single replay from standard input and print it as JSON
+  --scan    Scan a replay directory (repeatable) and print stats
+  --tag     Connect code to report wins and losses for, e.g. ABC#123
+  --json    Print the scan stats as JSON instead of text
+  --dump    Print every scanned replay as JSON instead of stats";
+
+/// Options for `--scan`.
+struct ScanArgs {
+    dirs: Vec<String>,
+    tag: Option<String>,
+    json: bool,
+    dump: bool,
+}
+
+impl ScanArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut scan = ScanArgs {
+            dirs: Vec::new(),
+            tag: None,
+            json: false,
+            dump: false,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scan" => scan
+                    .dirs
+                    .push(args.next().ok_or("--scan needs a directory")?.clone()),
+                "--tag" => {
+                    let tag = args.next().ok_or("--tag needs a connect code")?;
+                    scan.tag = Some(normalize_tag(tag));
+                }
+                "--json" => scan.json = true,
+                "--dump" => scan.dump = true,
+                other => return Err(format!("Unknown argument: {other}")),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+/// Run the CLI if `args` (without the program name) ask for it.
+///
+/// Returns the process exit code, or `None` when no CLI flags were given and the GUI should
+/// start as usual.
+pub fn run(args: impl IntoIterator<Item = String>) -> Option<i32> {
+    let args: Vec<String> = args.into_iter().collect();
+    match args.first().map(String::as_str) {
+        None => None,
+        Some("--stdin") => Some(parse_stdin()),
+        Some("--scan" | "--tag" | "--json" | "--dump") => match ScanArgs::parse(&args) {
+            Ok(scan) if !scan.dirs.is_empty() => Some(scan_dirs(&scan)),
+            Ok(_) => {
+                eprintln!("--scan needs a directory\n\n{USAGE}");
+                Some(2)
+            }
+            Err(e) => {
+                eprintln!("{e}\n\n{USAGE}");
+                Some(2)
+            }
+        },
+        Some("--help" | "-h") => {
+            println!("{USAGE}");
+            Some(0)
+        }
+        Some(other) => {
+            eprintln!("Unknown argument: {other}\n\n{USAGE}");
+            Some(2)
+        }
+    }
+}
+
+/// `cat game.slp | eppi --stdin`
+fn parse_stdin() -> i32 {
+    // `slippi::read` needs to seek, so buffer the whole replay first
+    let mut bytes = Vec::new();
+    if let Err(e) = io::stdin().lock().read_to_end(&mut bytes) {
+        eprintln!("Failed to read stdin: {e}");
+        return 1;
+    }
+
+    match parse_replay_from_reader(io::Cursor::new(bytes)) {
+        Ok(replay) => {
+            println!("{:#}", replay_summary(&replay));
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+/// Scan progress on one stderr line that keeps being overwritten. Skipped when stderr isn't a
+/// terminal, so logs and pipes don't fill up with it.
+fn print_progress(progress: ScanProgress) {
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() {
+        return;
+    }
+    let _ = match progress {
+        ScanProgress::Enumerating { found } => write!(stderr, "\rFinding replays... {found}"),
+        // Parsing reports every file; redraw every 64 and at the end
+        ScanProgress::Parsing { done, total } if done % 64 == 0 || done == total => {
+            write!(stderr, "\r\x1b[KParsing replays... {done}/{total}")
+        }
+        ScanProgress::Parsing { .. } => return,
+    };
+    let _ = stderr.flush();
+}
+
+/// `eppi --scan <dir> --tag <code>`: the GUI's scan and stats, printed to stdout.
+fn scan_dirs(scan: &ScanArgs) -> i32 {
+    let mut analyzer = ReplayAnalyzer::new();
+    let never_cancel = AtomicBool::new(false);
+    let scanned = analyzer.scan_directories(&scan.dirs, &never_cancel, print_progress, |_| {});
+    if io::stderr().is_terminal() {
+        eprintln!();
+    }
+    if let Err(e) = scanned {
+        eprintln!("Scan failed: {e}");
+        return 1;
+    }
+    for (path, error) in &analyzer.walk_errors {
+        eprintln!("Could not read {path}: {error}");
+    }
+
+    if scan.dump {
+        return match analyzer.to_json() {
+            Ok(json) => {
+                println!("{json}");
+                0
+            }
+            Err(e) => {
+                eprintln!("Failed to serialize replays: {e}");
+                1
+            }
+        };
+    }
+
+    let stats = scan_stats(&analyzer, scan.tag.as_deref());
+    if scan.json {
+        println!("{stats:#}");
+    } else {
+        print_scan_stats(&stats);
+    }
+    0
+}
+
+fn scan_stats(analyzer: &ReplayAnalyzer, tag: Option<&str>) -> serde_json::Value {
+    let mut stats = serde_json::json!({ "replays": analyzer.replays.len() });
+    let Some(tag) = tag else {
+        return stats;
+    };
+
+    let (wins, losses) = analyzer.get_stats_for_player(tag, "");
+    let record = |(character, (wins, losses)): (String, (usize, usize))| serde_json::json!({ "name": character, "wins": wins, "losses": losses });
+    let mut characters: Vec<_> = analyzer
+        .get_stats_by_character(tag)
+        .into_iter()
+        .map(|(id, record)| (character_id_to_name(id), record))
+        .collect();
+    characters.sort_by_key(|(_, (w, l))| std::cmp::Reverse(w + l));
+    let mut stages: Vec<_> = analyzer
+        .get_stats_by_stage(tag)
+        .into_iter()
+        .map(|(id, record)| (stage_id_to_name(id), record))
+        .collect();
+    stages.sort_by_key(|(_, (w, l))| std::cmp::Reverse(w + l));
+
+    stats["tag"] = tag.into();
+    stats["wins"] = wins.into();
+    stats["losses"] = losses.into();
+    stats["draws"] = analyzer.get_draws_for_player(tag, "").into();
+    stats["current_streak"] = analyzer.current_streak(tag).into();
+    stats["longest_win_streak"] = analyzer.longest_win_streak(tag).into();
+    stats["characters"] = characters.into_iter().map(record).collect();
+    stats["stages"] = stages.into_iter().map(record).collect();
+    stats
+}
+
+fn print_scan_stats(stats: &serde_json::Value) {
+    println!("Replays: {}", stats["replays"]);
+    let Some(tag) = stats["tag"].as_str() else {
+        return;
+    };
+
+    let wins = stats["wins"].as_u64().unwrap_or(0);
+    let losses = stats["losses"].as_u64().unwrap_or(0);
+    let win_rate = if wins + losses > 0 {
+        wins as f64 / (wins + losses) as f64 * 100.0
+    } else {
+        0.0
+    };
+    println!(
+        "{tag}: {wins}-{losses} ({win_rate:.1}%), {} draws/no contests",
+        stats["draws"]
+    );
+    println!(
+        "Current streak: {}, longest win streak: {}",
+        stats["current_streak"], stats["longest_win_streak"]
+    );
+    for section in ["characters", "stages"] {
+        println!("\nBy {}:", section.trim_end_matches('s'));
+        for row in stats[section].as_array().into_iter().flatten() {
+            println!(
+                "  {:<24} {}-{}",
+                row["name"].as_str().unwrap_or_default(),
+                row["wins"],
+                row["losses"]
+            );
+        }
+    }
+}
+
+fn replay_summary(replay: &ReplayInfo) -> serde_json::Value {
+    let player = |p: &PlayerInfo| {
+        serde_json::json!({
+            "name": p.name,
+            "display_name": p.display_name,
+            "character": p.character,
+            "character_name": p.character_name,
+            "port": p.port as u8 + 1,
+            "team": p.team,
+        })
+    };
+    let result = match replay.result {
+        GameResult::Player1Won => "player1",
+        GameResult::Player2Won => "player2",
+        GameResult::Draw => "draw",
+        GameResult::NoContest => "no_contest",
+        GameResult::Unknown => "unknown",
+    };
+
+    serde_json::json!({
+        "player1": player(replay.player1()),
+        "player2": player(replay.player2()),
+        "players": replay.players.iter().map(player).collect::<Vec<_>>(),
+        "result": result,
+        "stage": replay.stage,
+        "stage_name": replay.stage_name,
+        "duration_frames": replay.duration,
+        "start_time": replay.start_time.map(iso_8601::format),
+    })
+}
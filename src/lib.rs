@@ -3,7 +3,10 @@
 mod app;
 pub use app::Eppi;
 
+pub mod cli;
+
 mod peppi;
 
 pub mod ui;
-mod web;
+mod watch;
+pub mod web;
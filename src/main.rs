@@ -7,6 +7,11 @@
 async fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    // Headless CLI flags (e.g. `--stdin`) skip the GUI entirely
+    if let Some(exit_code) = eppi::cli::run(std::env::args().skip(1)) {
+        std::process::exit(exit_code);
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1280.0, 720.0])
@@ -1,258 +1,2165 @@
+use peppi::frame::immutable::PortData;
 use peppi::game::immutable::Game;
-use peppi::game::Port;
+use peppi::game::{EndMethod, Port};
 use peppi::io::slippi;
 use rayon::prelude::*;
 use rayon::slice::ParallelSliceMut;
 use std::collections::HashMap;
 use std::fs;
-use std::io;
+use std::hash::Hash;
+use std::io::{self, Write};
 use std::panic;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Mutex;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use walkdir::WalkDir;
 
 // Re-export web-related helpers so existing code (e.g. in `app.rs`) keeps compiling
-pub use crate::web::fetch_player_rank;
+pub use crate::web::{
+    rank_ordinal, RankInfo, RankProvider, SlippiRankProvider, WebConfig, RANK_TIERS,
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ReplayInfo {
-    pub player1: PlayerInfo,
-    pub player2: PlayerInfo,
+    /// Path of the replay file; empty when parsed from a reader.
+    pub file_path: String,
+    /// Everyone who played, in port order. Always at least two; four in doubles.
+    pub players: Vec<PlayerInfo>,
     pub result: GameResult,
+    pub end_method: GameEndMethod,
+    #[serde(default)]
+    pub mode: GameMode,
+    pub stage: u16,
     pub stage_name: String,
+    /// Playable frames, from "GO" to the last frame, so it matches the in-game timer.
     pub duration: Option<i32>,
+    /// Replay format version from Game Start, e.g. "3.14.0".
+    #[serde(default)]
+    pub format_version: Option<String>,
+    /// Date shown and sorted on, resolved from the fields below by [`DateSource`].
+    #[serde(with = "iso_8601")]
     pub date: Option<SystemTime>,
-    pub opponent_rank: Option<String>,
+    /// Match start time from the replay metadata (`startAt`).
+    #[serde(with = "iso_8601")]
+    pub start_time: Option<SystemTime>,
+    /// File modification time.
+    #[serde(with = "iso_8601")]
+    pub modified: Option<SystemTime>,
+    /// Filled in from the rank cache, so not part of the replay itself.
+    #[serde(skip)]
+    pub opponent_rank: Option<RankInfo>,
+    /// Whether [`ReplayInfo::analyze_frames`] has filled in the per-frame stats.
+    #[serde(skip)]
+    pub frames_analyzed: bool,
 }
 
-#[derive(Debug, Clone)]
+/// Where [`ReplayInfo::date`] comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum DateSource {
+    /// Metadata start time, or the file modification time when it's missing.
+    #[default]
+    PreferMetadata,
+    /// Metadata start time only; replays without it have no date.
+    Metadata,
+    /// File modification time only.
+    FileModified,
+}
+
+impl DateSource {
+    pub const ALL: [DateSource; 3] = [
+        DateSource::PreferMetadata,
+        DateSource::Metadata,
+        DateSource::FileModified,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DateSource::PreferMetadata => "Start time, else file date",
+            DateSource::Metadata => "Start time only",
+            DateSource::FileModified => "File modified date",
+        }
+    }
+
+    pub fn resolve(self, replay: &ReplayInfo) -> Option<SystemTime> {
+        match self {
+            DateSource::PreferMetadata => replay.start_time.or(replay.modified),
+            DateSource::Metadata => replay.start_time,
+            DateSource::FileModified => replay.modified,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct PlayerInfo {
+    /// Connect code (e.g. `BEAN#888`), or "Unknown". Stats match on this.
     pub name: String,
+    /// Slippi display name, which isn't unique, so only for showing.
+    pub display_name: Option<String>,
+    /// In-game nametag, if one was picked. Stats fall back to matching this for players
+    /// without a connect code (see [`ReplayInfo::player_index`]).
+    #[serde(default)]
+    pub name_tag: Option<String>,
+    /// Melee internal character ID from the game start block.
+    pub character: Option<u8>,
+    /// Display name for `character`, from [`character_id_to_name`].
+    pub character_name: String,
+    /// Serialized as the port number, 1 to 4.
+    #[serde(with = "port_number")]
+    pub port: Port,
+    /// Team color in teams games.
+    pub team: Option<u8>,
+    /// Stocks left on the last frame. `None` if the game was quit out of or has no frame data.
+    pub final_stocks: Option<u8>,
+    /// Actions per minute, from [`ReplayInfo::analyze_frames`].
+    #[serde(skip)]
+    pub apm: Option<f32>,
+    /// Successful L-cancels and total aerial landings, from [`ReplayInfo::analyze_frames`].
+    #[serde(skip)]
+    pub l_cancels: Option<(u32, u32)>,
+    /// Percent dealt to opponents over the game, from [`ReplayInfo::analyze_frames`].
+    #[serde(skip)]
+    pub damage_dealt: Option<f32>,
+    /// Conversions this player started (see [`walk_conversions`]), from
+    /// [`ReplayInfo::analyze_frames`].
+    #[serde(skip)]
+    pub openings: Option<u32>,
+    /// Stocks where this player landed the first hit, i.e. won the first neutral exchange,
+    /// from [`ReplayInfo::analyze_frames`].
+    #[serde(skip)]
+    pub first_hits: Option<u32>,
+}
+
+/// Who "me" is when reading a replay from one player's side: a connect code, and optionally
+/// the in-game nametag they use where they have no code (offline, or netplay without one).
+/// See [`ReplayInfo::player_index`] for which one wins when both could match.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlayerTag<'a> {
+    pub connect_code: &'a str,
+    /// Empty to match on the connect code alone.
+    pub name_tag: &'a str,
+}
+
+impl<'a> PlayerTag<'a> {
+    pub fn new(connect_code: &'a str, name_tag: &'a str) -> Self {
+        Self {
+            connect_code,
+            name_tag,
+        }
+    }
+
+    /// Neither a connect code nor a nametag, so nobody is "me".
+    pub fn is_empty(&self) -> bool {
+        self.connect_code.trim().is_empty() && self.name_tag.trim().is_empty()
+    }
+}
+
+/// Just a connect code.
+impl<'a> From<&'a str> for PlayerTag<'a> {
+    fn from(connect_code: &'a str) -> Self {
+        Self::new(connect_code, "")
+    }
+}
+
+impl<'a> From<&'a String> for PlayerTag<'a> {
+    fn from(connect_code: &'a String) -> Self {
+        Self::new(connect_code, "")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum GameResult {
+    Player1Won,
+    Player2Won,
+    /// Both sides share first place, e.g. a timeout with equal stocks and percent.
+    Draw,
+    /// The game was quit out of (LRAS) rather than finished.
+    NoContest,
+    Unknown,
+}
+
+impl GameResult {
+    /// Draws and no-contests have no winner but were still played.
+    pub fn is_draw_or_no_contest(&self) -> bool {
+        matches!(self, GameResult::Draw | GameResult::NoContest)
+    }
+}
+
+/// How a game ended, from the game end block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum GameEndMethod {
+    /// Someone ran out of stocks.
+    Stocks,
+    /// The timer ran out.
+    Timeout,
+    /// Someone quit out with L+R+A+Start.
+    Lras,
+    /// No end block, or an end code we don't recognize.
+    #[default]
+    Unknown,
+}
+
+impl GameEndMethod {
+    fn from_end(end: Option<&peppi::game::End>) -> Self {
+        match end.map(|end| end.method) {
+            Some(EndMethod::Game | EndMethod::Resolved) => GameEndMethod::Stocks,
+            Some(EndMethod::Time) => GameEndMethod::Timeout,
+            Some(EndMethod::NoContest) => GameEndMethod::Lras,
+            Some(EndMethod::Unresolved) | None => GameEndMethod::Unknown,
+        }
+    }
+}
+
+/// Which Slippi online mode a game was played in, from the match ID (Slippi 3.14+).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum GameMode {
+    Ranked,
+    Unranked,
+    /// Direct connect with a friend's code.
+    Direct,
+    Teams,
+    /// Local games, replays from before match IDs, or a mode we don't recognize.
+    #[default]
+    Unknown,
+}
+
+impl GameMode {
+    /// Match IDs look like `mode.ranked-2024-05-11T19:23:45.12-0`.
+    fn from_match(r#match: Option<&peppi::game::Match>) -> Self {
+        let Some(mode) = r#match.and_then(|m| m.id.strip_prefix("mode.")) else {
+            return GameMode::Unknown;
+        };
+        match mode.split('-').next() {
+            Some("ranked") => GameMode::Ranked,
+            Some("unranked") => GameMode::Unranked,
+            Some("direct") => GameMode::Direct,
+            Some("teams") => GameMode::Teams,
+            _ => GameMode::Unknown,
+        }
+    }
+}
+
+impl ReplayInfo {
+    /// Re-read the replay and compute the stats that need every frame (see the `#[serde(skip)]`
+    /// fields on [`PlayerInfo`]). Too slow for a whole scan, so it's done for one replay at a
+    /// time; does nothing if it already ran.
+    pub fn analyze_frames(&mut self) -> io::Result<()> {
+        if self.frames_analyzed {
+            return Ok(());
+        }
+        if self.file_path.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Replay has no file to read frames from",
+            ));
+        }
+        let game = read_game(open_replay_file(Path::new(&self.file_path))?)?;
+
+        let damage_taken: Vec<(Port, Option<u8>, f32)> = self
+            .players
+            .iter()
+            .filter_map(|player| {
+                let port_data = game.frames.ports.iter().find(|p| p.port == player.port)?;
+                let taken = damage_taken(port_data.leader.post.percent.values());
+                Some((player.port, player.team, taken))
+            })
+            .collect();
+        let conversions = walk_conversions(&game.frames.ports);
+        let first_hits = conversions.first_hits();
+
+        for player in &mut self.players {
+            let Some(port_data) = game.frames.ports.iter().find(|p| p.port == player.port) else {
+                continue;
+            };
+            let post = &port_data.leader.post;
+            player.apm = actions_per_minute(port_data.leader.pre.buttons_physical.values());
+            player.l_cancels = post
+                .l_cancel
+                .as_ref()
+                .and_then(|l_cancel| l_cancel_counts(post.state.values(), l_cancel.values()));
+            player.damage_dealt = Some(
+                damage_taken
+                    .iter()
+                    .filter(|(port, team, _)| {
+                        *port != player.port && (team.is_none() || *team != player.team)
+                    })
+                    .map(|(_, _, taken)| taken)
+                    .sum(),
+            );
+            player.openings = Some(
+                conversions
+                    .openings
+                    .iter()
+                    .filter(|opening| opening.attacker == player.port)
+                    .count() as u32,
+            );
+            player.first_hits = Some(
+                first_hits
+                    .iter()
+                    .filter(|&&port| port == player.port)
+                    .count() as u32,
+            );
+        }
+        self.frames_analyzed = true;
+        Ok(())
+    }
+
+    pub fn player1(&self) -> &PlayerInfo {
+        &self.players[0]
+    }
+
+    pub fn player2(&self) -> &PlayerInfo {
+        &self.players[1]
+    }
+
+    pub fn is_teams(&self) -> bool {
+        self.players.len() > 2
+    }
+
+    /// A singles game where both players picked the same character.
+    pub fn is_ditto(&self) -> bool {
+        !self.is_teams()
+            && self.player1().character.is_some()
+            && self.player1().character == self.player2().character
+    }
+
+    /// Which side a player is on; see [`side_of`].
+    fn side_of(&self, index: usize) -> usize {
+        side_of(&self.players, index)
+    }
+
+    /// Stocks left on each side at the end, like "3-0" (player1's side first). `None` if any
+    /// player's count is unknown.
+    pub fn stock_differential(&self) -> Option<String> {
+        let mut totals = [0u32; 2];
+        for (i, player) in self.players.iter().enumerate() {
+            totals[self.side_of(i)] += u32::from(player.final_stocks?);
+        }
+        Some(format!("{}-{}", totals[0], totals[1]))
+    }
+
+    /// Players grouped by side; `[player1's side, the other side]`.
+    pub fn sides(&self) -> [Vec<&PlayerInfo>; 2] {
+        let mut sides = [Vec::new(), Vec::new()];
+        for (i, player) in self.players.iter().enumerate() {
+            sides[self.side_of(i)].push(player);
+        }
+        sides
+    }
+
+    /// Returns `(me, opponent)` if `player_tag` played. In doubles, `opponent` is the first
+    /// player on the other side.
+    pub fn perspective<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+    ) -> Option<(&PlayerInfo, &PlayerInfo)> {
+        let index = self.player_index(player_tag)?;
+        let side = self.side_of(index);
+        let opponent = (0..self.players.len()).find(|&i| self.side_of(i) != side)?;
+        Some((&self.players[index], &self.players[opponent]))
+    }
+
+    /// Index of the player `player_tag` refers to. The connect code takes precedence: only when
+    /// no player has it is the nametag matched against in-game nametags, which is how offline
+    /// games and netplay without a code identify players.
+    pub fn player_index<'a>(&self, player_tag: impl Into<PlayerTag<'a>>) -> Option<usize> {
+        let player_tag = player_tag.into();
+        self.players
+            .iter()
+            .position(|p| same_tag(&p.name, player_tag.connect_code))
+            .or_else(|| {
+                if player_tag.name_tag.trim().is_empty() {
+                    return None;
+                }
+                self.players.iter().position(|p| {
+                    p.name_tag
+                        .as_ref()
+                        .is_some_and(|name_tag| same_tag(name_tag, player_tag.name_tag))
+                })
+            })
+    }
+
+    /// Case-insensitive search over the player tags, stage name and opponent rank. `query`
+    /// must already be lowercase.
+    pub fn matches_search(&self, query: &str) -> bool {
+        query.is_empty()
+            || self.players.iter().any(|p| {
+                p.name.to_lowercase().contains(query)
+                    || [&p.display_name, &p.name_tag]
+                        .into_iter()
+                        .flatten()
+                        .any(|name| name.to_lowercase().contains(query))
+            })
+            || self.stage_name.to_lowercase().contains(query)
+            || self
+                .opponent_rank
+                .as_ref()
+                .is_some_and(|rank| rank.rank.to_lowercase().contains(query))
+    }
+
+    /// Whether `date` is only the file's modified time, which changes when replays are copied.
+    pub fn date_is_approximate(&self) -> bool {
+        self.date.is_some() && self.date != self.start_time
+    }
+
+    /// Whether `player_tag` won this game. `None` if they didn't play or the result is unknown.
+    pub fn won_by<'a>(&self, player_tag: impl Into<PlayerTag<'a>>) -> Option<bool> {
+        let player1_won = match self.result {
+            GameResult::Player1Won => true,
+            GameResult::Player2Won => false,
+            GameResult::Draw | GameResult::NoContest | GameResult::Unknown => return None,
+        };
+        let index = self.player_index(player_tag)?;
+        Some((self.side_of(index) == 0) == player1_won)
+    }
+}
+
+/// Progress reported by [`ReplayAnalyzer::scan_directories`]. Also serves as a heartbeat:
+/// enumeration reports periodically even when no new replays turn up.
+#[derive(Debug, Clone, Copy)]
+pub enum ScanProgress {
+    /// Still walking the directory tree, with `found` replays so far.
+    Enumerating { found: usize },
+    /// Enumeration finished; `done` of `total` replays parsed so far.
+    Parsing { done: usize, total: usize },
+}
+
+/// How replay directories are walked, by scans, refreshes and the watcher alike.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// How many folders deep to look for replays; `None` for no limit. Slippi's own layout
+    /// (monthly folders in the replay directory) needs 2.
+    pub max_depth: Option<usize>,
+    /// Hidden or system folder names to look in anyway, e.g. `.replays`. Others are skipped;
+    /// see [`is_hidden_dir`].
+    pub include_hidden: Vec<String>,
+}
+
+impl ScanOptions {
+    /// Every entry under `dir`, within these limits. Hidden and system folders below `dir` are
+    /// skipped without reading them, unless listed in `include_hidden`.
+    pub fn walk(
+        &self,
+        dir: impl AsRef<Path>,
+    ) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + '_ {
+        let mut walk = WalkDir::new(dir);
+        if let Some(max_depth) = self.max_depth {
+            walk = walk.max_depth(max_depth);
+        }
+        walk.into_iter().filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_type().is_dir() || !self.skips_dir(entry.path())
+        })
+    }
+
+    /// Whether `path`, somewhere under `dir`, is within these limits, i.e. one that
+    /// [`Self::walk`] would visit. For checking paths that turn up some other way, like file
+    /// system events.
+    pub fn covers(&self, dir: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(dir) else {
+            return false;
+        };
+        let depth = relative.components().count();
+        if self.max_depth.is_some_and(|max_depth| depth > max_depth) {
+            return false;
+        }
+        // Every folder between `dir` and `path`
+        let mut folder = dir.to_path_buf();
+        relative
+            .components()
+            .take(depth.saturating_sub(1))
+            .all(|component| {
+                folder.push(component);
+                !self.skips_dir(&folder)
+            })
+    }
+
+    /// Whether the folder at `path` is hidden and not listed in `include_hidden`.
+    fn skips_dir(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default();
+        is_hidden_dir(path)
+            && !self
+                .include_hidden
+                .iter()
+                .any(|include| name.eq_ignore_ascii_case(include.trim()))
+    }
+}
+
+/// `(path, error)` for a directory entry that couldn't be read, without the path repeated in
+/// the error.
+pub(crate) fn walk_error(e: &walkdir::Error) -> (String, String) {
+    let path = e.path().map_or_else(
+        || "(unknown path)".to_string(),
+        |path| path.display().to_string(),
+    );
+    let error = match e.io_error() {
+        Some(io_error) => io_error.to_string(),
+        None => e.to_string(),
+    };
+    (path, error)
+}
+
+/// Folders a replay scan has no business in: dotfolders like `.git` and `.Trash`, Windows
+/// system folders like `$RECYCLE.BIN` and `System Volume Information`, and on Windows anything
+/// marked hidden or system. `path` must be a folder.
+fn is_hidden_dir(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if name.starts_with('.') || name.starts_with('$') || name == "System Volume Information" {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            return metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM)
+                != 0;
+        }
+    }
+    false
+}
+
+/// How many directory entries to visit between [`ScanProgress::Enumerating`] reports.
+const ENUMERATION_HEARTBEAT: usize = 256;
+
+/// Gap between games that starts a new session in [`ReplayAnalyzer::group_into_sessions`].
+pub const DEFAULT_SESSION_GAP: Duration = Duration::from_secs(3_600);
+
+/// Connect code of the "you" player in [`ReplayAnalyzer::load_sample_data`].
+pub const SAMPLE_CONNECT_CODE: &str = "EPPI#001";
+
+/// How long a fetched rank is trusted before it's looked up again.
+pub const DEFAULT_RANK_CACHE_TTL: Duration = Duration::from_secs(24 * 3_600);
+/// Failed lookups are retried sooner so a transient error doesn't stick.
+pub const FAILED_RANK_CACHE_TTL: Duration = Duration::from_secs(3_600);
+
+/// Bumped whenever parsing extracts something new, so cached replays missing it are re-parsed.
+const PARSE_CACHE_FORMAT: u32 = 6;
+
+/// A parsed replay kept in the parse cache, reused while the file's size and modified time
+/// still match.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ParseCacheEntry {
+    /// [`PARSE_CACHE_FORMAT`] when this was parsed; 0 for entries from before it existed.
+    #[serde(default)]
+    format: u32,
+    size: u64,
+    modified: Option<SystemTime>,
+    replay: ReplayInfo,
+}
+
+/// The replay list from the last scan, restored on startup so the table fills before the
+/// rescan finishes.
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+struct SavedReplays<R> {
+    /// [`PARSE_CACHE_FORMAT`] when this was saved; older lists are dropped.
+    #[serde(default)]
+    format: u32,
+    replays: R,
+}
+
+/// A looked-up rank and when it was fetched.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct CachedRank {
+    pub rank: RankInfo,
+    pub fetched_at: SystemTime,
+    /// The lookup failed and `rank` is just a placeholder.
+    #[serde(default)]
+    pub failed: bool,
+}
+
+pub struct ReplayAnalyzer {
+    pub replays: Vec<ReplayInfo>,
+    pub rank_cache: HashMap<String, CachedRank>, // Cache for player tag -> rank
+    /// Where [`Self::save_rank_cache`] writes the rank cache; `None` keeps it in memory only.
+    pub rank_cache_path: Option<PathBuf>,
+    /// Cached ranks older than this are looked up again; see [`Self::get_fresh_rank`].
+    pub cache_ttl: Duration,
+    pub date_source: DateSource,
+    /// Replays the last scan couldn't parse: `(path, error)`.
+    pub parse_failures: Vec<(String, String)>,
+    /// Folders (or files) the last scan couldn't read, e.g. for lack of permission:
+    /// `(path, error)`. Replays in them are missing from the results.
+    pub walk_errors: Vec<(String, String)>,
+    /// Only count games on [tournament-legal](is_tournament_legal) stages in the breakdowns
+    /// ([`Self::tally_by`] and [`Self::session_records`]).
+    pub legal_stages_only: bool,
+    /// Games shorter than this many frames are left out of the streaks,
+    /// [`Self::stats_since`] and the breakdowns; `None` counts every game.
+    pub min_game_frames: Option<i32>,
+    /// How [`Self::scan_directories`] walks the directories.
+    pub scan_options: ScanOptions,
+}
+
+impl ReplayAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            replays: Vec::new(),
+            rank_cache: HashMap::new(),
+            rank_cache_path: None,
+            cache_ttl: DEFAULT_RANK_CACHE_TTL,
+            date_source: DateSource::default(),
+            parse_failures: Vec::new(),
+            walk_errors: Vec::new(),
+            legal_stages_only: false,
+            min_game_frames: None,
+            scan_options: ScanOptions::default(),
+        }
+    }
+
+    /// An analyzer whose rank cache is loaded from and saved to `path`. A missing or corrupt
+    /// file just starts an empty cache.
+    pub fn with_cache(path: PathBuf) -> Self {
+        let rank_cache = load_json_cache(&path);
+        Self {
+            rank_cache,
+            rank_cache_path: Some(path),
+            ..Self::new()
+        }
+    }
+
+    /// Write the rank cache to [`Self::rank_cache_path`], if there is one.
+    pub fn save_rank_cache(&self) -> io::Result<()> {
+        match &self.rank_cache_path {
+            Some(path) => save_json_cache(path, &self.rank_cache),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the loaded replays to [`replay_list_path`] for [`Self::load_replay_list`].
+    pub fn save_replay_list(&self) -> io::Result<()> {
+        save_json_cache(
+            &replay_list_path(),
+            &SavedReplays {
+                format: PARSE_CACHE_FORMAT,
+                replays: &self.replays,
+            },
+        )
+    }
+
+    /// Replace the loaded replays with the list saved by [`Self::save_replay_list`]. Returns how
+    /// many were restored; 0 if there's no saved list or it's from an older format.
+    pub fn load_replay_list(&mut self) -> usize {
+        let saved: SavedReplays<Vec<ReplayInfo>> = load_json_cache(&replay_list_path());
+        if saved.format != PARSE_CACHE_FORMAT {
+            return 0;
+        }
+        self.replays = saved.replays;
+        self.set_date_source(self.date_source);
+        self.replays.len()
+    }
+
+    /// Switch where replay dates come from, re-resolving and re-sorting the loaded replays.
+    pub fn set_date_source(&mut self, source: DateSource) {
+        self.date_source = source;
+        for replay in &mut self.replays {
+            replay.date = source.resolve(replay);
+        }
+        self.sort_by_date();
+    }
+
+    /// Sort by date (newest first) in parallel
+    fn sort_by_date(&mut self) {
+        self.replays.par_sort_unstable_by(|a, b| {
+            match (a.date, b.date) {
+                (Some(date_a), Some(date_b)) => date_b.cmp(&date_a), // Newer first
+                (Some(_), None) => std::cmp::Ordering::Less,         // Files with dates come first
+                (None, Some(_)) => std::cmp::Ordering::Greater, // Files without dates come last
+                (None, None) => std::cmp::Ordering::Equal,      // Equal if both have no date
+            }
+        });
+    }
+
+    /// Parse every replay under `dirs`, reporting progress as it goes. Each replay is also
+    /// handed to `on_replay` as soon as it's parsed (from the worker threads, in no particular
+    /// order) so callers can show results before the scan finishes. Setting `cancel` stops
+    /// the scan early: while files are still being found, with an
+    /// [`io::ErrorKind::Interrupted`] error that leaves `self` untouched; once parsing has
+    /// started, successfully with whatever was parsed so far.
+    pub fn scan_directories(
+        &mut self,
+        dirs: &[String],
+        cancel: &AtomicBool,
+        progress: impl Fn(ScanProgress) + Sync,
+        on_replay: impl Fn(&ReplayInfo) + Sync,
+    ) -> io::Result<()> {
+        if let Some(dir) = missing_dir(dirs) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Directory not found: {dir}"),
+            ));
+        }
+        let cache_dir = cache_dir();
+        let cache_path = bad_replay_cache_path();
+
+        let mut bad_cache = load_bad_replay_cache();
+
+        // First, collect all replay files, skipping those known to be bad
+        let ReplayFiles { files, walk_errors } =
+            find_replay_files(dirs, &self.scan_options, cancel, &progress)?;
+        self.walk_errors = walk_errors;
+        let (known_bad, slp_files): (Vec<PathBuf>, Vec<PathBuf>) = files
+            .into_iter()
+            .partition(|path| bad_cache.contains(path.to_string_lossy().as_ref()));
+
+        log::info!("Found {} replay files to process", slp_files.len());
+
+        // Previously parsed replays, keyed by path
+        let parse_cache: HashMap<String, ParseCacheEntry> = load_json_cache(&parse_cache_path());
+        let ParsedFiles {
+            parsed,
+            failed: new_bad_vec,
+            reparsed,
+        } = parse_replay_files(slp_files, &parse_cache, cancel, &progress, &on_replay)?;
+
+        let cancelled = cancel.load(Ordering::Relaxed);
+        let skipped_count = new_bad_vec.len();
+        log::info!(
+            "Successfully parsed {} replays ({reparsed} not cached, skipped {skipped_count})",
+            parsed.len()
+        );
+
+        self.replays = parsed
+            .iter()
+            .map(|(_, entry)| entry.replay.clone())
+            .collect();
+        self.set_date_source(self.date_source);
+
+        // Rewrite the parse cache when anything changed; this also drops deleted files. A
+        // cancelled scan didn't visit everything, so it only adds to the old cache.
+        let parse_cache_changed = reparsed > 0 || (!cancelled && parsed.len() != parse_cache.len());
+        let parse_cache: HashMap<String, ParseCacheEntry> = if cancelled {
+            let mut merged = parse_cache;
+            merged.extend(parsed);
+            merged
+        } else {
+            parsed.into_iter().collect()
+        };
+        if parse_cache_changed {
+            if let Err(e) = save_json_cache(&parse_cache_path(), &parse_cache) {
+                log::error!("Failed to write parse cache: {e}");
+            }
+        }
+
+        self.parse_failures = new_bad_vec.clone();
+        self.parse_failures
+            .extend(known_bad.into_iter().map(|path| {
+                let reason =
+                    "Failed in an earlier scan; clear the parse cache to retry".to_string();
+                (path.to_string_lossy().into_owned(), reason)
+            }));
+        self.parse_failures.sort();
+
+        if !new_bad_vec.is_empty() {
+            // Ensure cache dir exists
+            if let Err(e) = fs::create_dir_all(&cache_dir) {
+                log::error!("Failed to create cache directory {cache_dir:?}: {e}");
+            }
+            for (p, _) in new_bad_vec {
+                bad_cache.insert(p);
+            }
+            if let Some(parent) = cache_path.parent() {
+                if !parent.exists() {
+                    log::warn!("Parent directory {parent:?} does NOT exist – creating it");
+                    if let Err(e) = fs::create_dir_all(parent) {
+                        log::error!("Failed to create parent directory {parent:?}: {e}");
+                    }
+                }
+            }
+            let data = bad_cache.into_iter().collect::<Vec<_>>().join("\n");
+            log::info!("Caching {skipped_count} bad replay paths to {cache_path:?}");
+            if let Err(e) = fs::write(&cache_path, data) {
+                log::error!("Failed to update bad replay cache at {cache_path:?}: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse only the replays under `dirs` that aren't in `known` (by path), for picking up new
+    /// games without a full [`Self::scan_directories`]. Walks and parses like a scan, reusing
+    /// and adding to the parse cache, and reports progress the same way. Files that failed in
+    /// an earlier scan are skipped, and ones that fail now are just logged. Merge the replays
+    /// with [`Self::merge_replays`]; the walk errors replace [`Self::walk_errors`].
+    pub fn find_new_replays(
+        dirs: &[String],
+        options: &ScanOptions,
+        known: &std::collections::HashSet<String>,
+        progress: impl Fn(ScanProgress) + Sync,
+    ) -> NewReplays {
+        let never_cancel = AtomicBool::new(false);
+        let ReplayFiles { files, walk_errors } =
+            match find_replay_files(dirs, options, &never_cancel, &progress) {
+                Ok(found) => found,
+                Err(e) => {
+                    log::warn!("Couldn't look for new replays: {e}");
+                    return NewReplays::default();
+                }
+            };
+        let bad_cache = load_bad_replay_cache();
+        let new_files: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|path| {
+                let path = path.to_string_lossy();
+                !known.contains(path.as_ref()) && !bad_cache.contains(path.as_ref())
+            })
+            .collect();
+        if new_files.is_empty() {
+            return NewReplays {
+                replays: Vec::new(),
+                walk_errors,
+            };
+        }
+
+        let mut parse_cache: HashMap<String, ParseCacheEntry> =
+            load_json_cache(&parse_cache_path());
+        let parsed =
+            match parse_replay_files(new_files, &parse_cache, &never_cancel, &progress, &|_| {}) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    log::warn!("Couldn't parse new replays: {e}");
+                    return NewReplays {
+                        replays: Vec::new(),
+                        walk_errors,
+                    };
+                }
+            };
+        for (path, error) in &parsed.failed {
+            log::warn!("Failed to parse new replay {path}: {error}");
+        }
+
+        let replays: Vec<ReplayInfo> = parsed
+            .parsed
+            .iter()
+            .map(|(_, entry)| entry.replay.clone())
+            .collect();
+        if parsed.reparsed > 0 {
+            parse_cache.extend(parsed.parsed);
+            if let Err(e) = save_json_cache(&parse_cache_path(), &parse_cache) {
+                log::error!("Failed to write parse cache: {e}");
+            }
+        }
+        NewReplays {
+            replays,
+            walk_errors,
+        }
+    }
+
+    /// Add replays from [`Self::find_new_replays`], skipping any already loaded, and re-sort.
+    /// Returns how many were added.
+    pub fn merge_replays(&mut self, replays: Vec<ReplayInfo>) -> usize {
+        let before = self.replays.len();
+        let known: std::collections::HashSet<String> =
+            self.replays.iter().map(|r| r.file_path.clone()).collect();
+        self.replays.extend(
+            replays
+                .into_iter()
+                .filter(|r| !known.contains(&r.file_path)),
+        );
+        self.set_date_source(self.date_source);
+        self.replays.len() - before
+    }
+
+    /// Replace the loaded replays with a small synthetic dataset, for screenshots and UI
+    /// testing without a Slippi folder. [`SAMPLE_CONNECT_CODE`] plays in every game.
+    pub fn load_sample_data(&mut self) {
+        // (opponent, rank, my character, their character, stage, I won, duration in frames)
+        const GAMES: [(&str, &str, u8, u8, u16, bool, i32); 10] = [
+            ("FOXY#123", "Diamond 2", 2, 2, 31, true, 7_420),
+            ("FOXY#123", "Diamond 2", 2, 2, 32, false, 5_310),
+            ("MRTH#404", "Gold 3", 2, 9, 8, true, 9_880),
+            ("MRTH#404", "Gold 3", 20, 9, 3, true, 11_205),
+            ("PUFF#777", "Master 1", 2, 15, 28, false, 14_460),
+            ("SHEK#010", "Silver 1", 20, 19, 2, true, 6_035),
+            ("PEAC#222", "Platinum 3", 2, 12, 31, false, 10_750),
+            ("ICIE#909", "Unranked", 20, 14, 3, true, 8_190),
+            ("FALC#555", "Bronze 3", 2, 20, 8, true, 4_870),
+            ("FALC#555", "Bronze 3", 2, 20, 32, false, 7_995),
+        ];
+
+        let now = SystemTime::now();
+        // Made-up opponents shouldn't end up in the real rank cache
+        self.rank_cache_path = None;
+        self.rank_cache.clear();
+        self.replays = GAMES
+            .iter()
+            .enumerate()
+            .map(|(i, &(opponent, rank, mine, theirs, stage, won, frames))| {
+                let start_time = now.checked_sub(Duration::from_secs(i as u64 * 5 * 3_600));
+                self.cache_rank(opponent.to_string(), RankInfo::unrated(rank));
+
+                // Alternate ports so both perspectives show up in the table
+                let (my_port, their_port, player1_won) = if i % 2 == 0 {
+                    (Port::P1, Port::P2, won)
+                } else {
+                    (Port::P2, Port::P1, !won)
+                };
+                let stocks_left = 1 + (i % 3) as u8;
+                let (my_stocks, their_stocks) = if won {
+                    (stocks_left, 0)
+                } else {
+                    (0, stocks_left)
+                };
+                let me = PlayerInfo {
+                    name: SAMPLE_CONNECT_CODE.to_string(),
+                    display_name: None,
+                    name_tag: None,
+                    character: Some(mine),
+                    character_name: character_id_to_name(mine),
+                    port: my_port,
+                    team: None,
+                    final_stocks: Some(my_stocks),
+                    apm: None,
+                    l_cancels: None,
+                    damage_dealt: None,
+                    openings: None,
+                    first_hits: None,
+                };
+                let them = PlayerInfo {
+                    name: opponent.to_string(),
+                    display_name: None,
+                    name_tag: None,
+                    character: Some(theirs),
+                    character_name: character_id_to_name(theirs),
+                    port: their_port,
+                    team: None,
+                    final_stocks: Some(their_stocks),
+                    apm: None,
+                    l_cancels: None,
+                    damage_dealt: None,
+                    openings: None,
+                    first_hits: None,
+                };
+                let mut players = vec![me, them];
+                players.sort_by_key(|p| p.port);
+
+                ReplayInfo {
+                    file_path: String::new(),
+                    players,
+                    result: if player1_won {
+                        GameResult::Player1Won
+                    } else {
+                        GameResult::Player2Won
+                    },
+                    end_method: GameEndMethod::Stocks,
+                    mode: GameMode::Ranked,
+                    stage,
+                    stage_name: stage_id_to_name(stage),
+                    duration: Some(frames),
+                    format_version: None,
+                    date: start_time,
+                    start_time,
+                    modified: start_time,
+                    opponent_rank: Some(RankInfo::unrated(rank)),
+                    frames_analyzed: false,
+                }
+            })
+            .collect();
+    }
+
+    /// Wins and losses for `player_tag` over the replays matching `query` (see
+    /// [`ReplayInfo::matches_search`]; pass `""` for all of them).
+    pub fn get_stats_for_player<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        query: &str,
+    ) -> (usize, usize) {
+        let player_tag = player_tag.into();
+        let mut wins = 0;
+        let mut losses = 0;
+
+        for replay in self.replays.iter().filter(|r| r.matches_search(query)) {
+            match replay.won_by(player_tag) {
+                Some(true) => wins += 1,
+                Some(false) => losses += 1,
+                None => {}
+            }
+        }
+
+        (wins, losses)
+    }
+
+    /// Whether `player_tag` played in any loaded replay.
+    pub fn played_in_any<'a>(&self, player_tag: impl Into<PlayerTag<'a>>) -> bool {
+        let player_tag = player_tag.into();
+        self.replays
+            .iter()
+            .any(|replay| replay.perspective(player_tag).is_some())
+    }
+
+    /// The tag in the most replays, excluding "Unknown", and how many it's in. In someone's own
+    /// replay folder that's almost always their connect code.
+    pub fn most_common_tag(&self) -> Option<(&str, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for player in self.replays.iter().flat_map(|r| &r.players) {
+            if player.name != "Unknown" {
+                *counts.entry(player.name.as_str()).or_default() += 1;
+            }
+        }
+        // Ties go to the alphabetically first tag, so the answer doesn't flicker
+        counts
+            .into_iter()
+            .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))
+    }
+
+    /// Distinct player tags across all replays, excluding "Unknown", sorted.
+    pub fn player_tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .replays
+            .iter()
+            .flat_map(|r| r.players.iter().map(|p| p.name.as_str()))
+            .filter(|name| *name != "Unknown")
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
+    /// Up to `limit` known tags matching `query` (case-insensitive), prefix matches first.
+    pub fn suggest_tags(&self, query: &str, limit: usize) -> Vec<String> {
+        let query = query.to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let (prefix, substring): (Vec<&str>, Vec<&str>) = self
+            .player_tags()
+            .into_iter()
+            .filter(|tag| tag.to_lowercase().contains(&query))
+            .partition(|tag| tag.to_lowercase().starts_with(&query));
+        prefix
+            .into_iter()
+            .chain(substring)
+            .take(limit)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Marks replays that are part of a run of two or more consecutive wins for `player_tag`,
+    /// taking them in `order` (indices into `replays`, e.g. as a table lists them). Any game
+    /// that isn't a win for them breaks the run; games that aren't
+    /// [long enough](Self::is_long_enough) are skipped.
+    pub fn win_streak_mask<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        order: impl IntoIterator<Item = usize>,
+    ) -> Vec<bool> {
+        let player_tag = player_tag.into();
+        let mut mask = vec![false; self.replays.len()];
+        let mut run = Vec::new();
+        let counted = order
+            .into_iter()
+            .map(|i| (i, &self.replays[i]))
+            .filter(|(_, r)| self.is_long_enough(r));
+        // `None` ends the last run
+        for game in counted.map(Some).chain([None]) {
+            match game {
+                Some((i, replay)) if replay.won_by(player_tag) == Some(true) => run.push(i),
+                _ => {
+                    if run.len() >= 2 {
+                        run.iter().for_each(|&i| mask[i] = true);
+                    }
+                    run.clear();
+                }
+            }
+        }
+        mask
+    }
+
+    /// Split the (date-sorted) replays into play sessions: runs of games where consecutive dates
+    /// are at most `gap` apart. Returns replay indices per session, in list order. A replay
+    /// without a date gets a session of its own.
+    pub fn group_into_sessions(&self, gap: Duration) -> Vec<Vec<usize>> {
+        let mut sessions: Vec<Vec<usize>> = Vec::new();
+        let mut previous: Option<SystemTime> = None;
+        for (i, replay) in self.replays.iter().enumerate() {
+            let continues = match (previous, replay.date) {
+                (Some(previous), Some(date)) => {
+                    let apart = previous
+                        .duration_since(date)
+                        .unwrap_or_else(|e| e.duration());
+                    apart <= gap
+                }
+                _ => false,
+            };
+            match sessions.last_mut() {
+                Some(session) if continues => session.push(i),
+                _ => sessions.push(vec![i]),
+            }
+            previous = replay.date;
+        }
+        sessions
+    }
+
+    /// Games, wins and losses for `player_tag` among the replays dated `since` or later that are
+    /// [long enough](Self::is_long_enough). Relies on the replays being sorted newest first.
+    pub fn stats_since<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        since: SystemTime,
+    ) -> (usize, usize, usize) {
+        let player_tag = player_tag.into();
+        self.replays
+            .iter()
+            .take_while(|r| r.date.is_some_and(|date| date >= since))
+            .filter(|r| self.is_long_enough(r))
+            .filter_map(|r| r.perspective(player_tag).map(|_| r.won_by(player_tag)))
+            .fold((0, 0, 0), |(games, wins, losses), won| match won {
+                Some(true) => (games + 1, wins + 1, losses),
+                Some(false) => (games + 1, wins, losses + 1),
+                None => (games + 1, wins, losses),
+            })
+    }
+
+    /// Date and record for `player_tag` of each play session (see
+    /// [`Self::group_into_sessions`]), oldest first. Sessions without a date or a decided game
+    /// are left out.
+    pub fn session_records<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        gap: Duration,
+    ) -> Vec<(SystemTime, usize, usize)> {
+        let player_tag = player_tag.into();
+        let mut records: Vec<_> = self
+            .group_into_sessions(gap)
+            .into_iter()
+            .filter_map(|session| {
+                let date = self.replays[session[0]].date?;
+                let (wins, losses) = session.iter().fold((0, 0), |(w, l), &i| {
+                    if !self.counts_in_breakdowns(&self.replays[i]) {
+                        return (w, l);
+                    }
+                    match self.replays[i].won_by(player_tag) {
+                        Some(true) => (w + 1, l),
+                        Some(false) => (w, l + 1),
+                        None => (w, l),
+                    }
+                });
+                (wins + losses > 0).then_some((date, wins, losses))
+            })
+            .collect();
+        records.sort_by_key(|&(date, _, _)| date);
+        records
+    }
+
+    /// Current streak for `player_tag` from the newest replay: positive for consecutive wins,
+    /// negative for consecutive losses. A game they didn't play in or that had no winner ends it;
+    /// games that aren't [long enough](Self::is_long_enough) are skipped.
+    pub fn current_streak<'a>(&self, player_tag: impl Into<PlayerTag<'a>>) -> i32 {
+        let player_tag = player_tag.into();
+        let mut results = self
+            .replays
+            .iter()
+            .filter(|r| self.is_long_enough(r))
+            .map(|r| r.won_by(player_tag));
+        let Some(Some(first)) = results.next() else {
+            return 0;
+        };
+        let length = 1 + results.take_while(|&won| won == Some(first)).count() as i32;
+        if first {
+            length
+        } else {
+            -length
+        }
+    }
+
+    /// Longest run of consecutive wins for `player_tag` anywhere in the list, skipping games
+    /// that aren't [long enough](Self::is_long_enough).
+    pub fn longest_win_streak<'a>(&self, player_tag: impl Into<PlayerTag<'a>>) -> usize {
+        let player_tag = player_tag.into();
+        let mut longest = 0;
+        let mut run = 0;
+        for replay in self.replays.iter().filter(|r| self.is_long_enough(r)) {
+            if replay.won_by(player_tag) == Some(true) {
+                run += 1;
+                longest = longest.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        longest
+    }
+
+    /// Number of draws and no-contests `player_tag` played in among the replays matching
+    /// `query`. These are left out of [`Self::get_stats_for_player`].
+    pub fn get_draws_for_player<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        query: &str,
+    ) -> usize {
+        let player_tag = player_tag.into();
+        self.replays
+            .iter()
+            .filter(|r| r.matches_search(query))
+            .filter(|r| r.result.is_draw_or_no_contest() && r.perspective(player_tag).is_some())
+            .count()
+    }
+
+    /// Copy cached ranks onto [`ReplayInfo::opponent_rank`] for games `player_tag` played, so
+    /// they can be searched.
+    pub fn fill_opponent_ranks<'a>(&mut self, player_tag: impl Into<PlayerTag<'a>>) {
+        let player_tag = player_tag.into();
+        let ranks: Vec<Option<RankInfo>> = self
+            .replays
+            .iter()
+            .map(|replay| {
+                let (_, opponent) = replay.perspective(player_tag)?;
+                self.get_fresh_rank(&opponent.name).cloned()
+            })
+            .collect();
+        for (replay, rank) in self.replays.iter_mut().zip(ranks) {
+            replay.opponent_rank = rank;
+        }
+    }
+
+    pub fn get_cached_rank(&self, player_tag: &str) -> Option<&RankInfo> {
+        self.rank_cache.get(player_tag).map(|cached| &cached.rank)
+    }
+
+    /// Like [`Self::get_cached_rank`], but `None` once the entry has outlived
+    /// [`Self::cache_ttl`] (or [`FAILED_RANK_CACHE_TTL`] for failed lookups).
+    pub fn get_fresh_rank(&self, player_tag: &str) -> Option<&RankInfo> {
+        let cached = self.rank_cache.get(player_tag)?;
+        let ttl = if cached.failed {
+            self.cache_ttl.min(FAILED_RANK_CACHE_TTL)
+        } else {
+            self.cache_ttl
+        };
+        // A fetched_at in the future (clock changes) counts as fresh
+        let age = cached.fetched_at.elapsed().unwrap_or_default();
+        (age < ttl).then_some(&cached.rank)
+    }
+
+    /// Remember `rank` for `player_tag` as fetched just now.
+    pub fn cache_rank(&mut self, player_tag: String, rank: RankInfo) {
+        let cached = CachedRank {
+            rank,
+            fetched_at: SystemTime::now(),
+            failed: false,
+        };
+        self.rank_cache.insert(player_tag, cached);
+    }
+
+    /// Remember that looking up `player_tag` failed, so it isn't retried until
+    /// [`FAILED_RANK_CACHE_TTL`] passes.
+    pub fn cache_rank_failure(&mut self, player_tag: String) {
+        let cached = CachedRank {
+            rank: RankInfo::unrated("Unknown"),
+            fetched_at: SystemTime::now(),
+            failed: true,
+        };
+        self.rank_cache.insert(player_tag, cached);
+    }
+
+    /// Forget every cached rank so the next lookup re-fetches it. Returns how many were removed.
+    pub fn clear_rank_cache(&mut self) -> usize {
+        let removed = self.rank_cache.len();
+        self.rank_cache.clear();
+        for replay in &mut self.replays {
+            replay.opponent_rank = None;
+        }
+        removed
+    }
+
+    /// Wins/losses for `player_tag`, bucketed by the character they played.
+    pub fn get_stats_by_character<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+    ) -> HashMap<u8, (usize, usize)> {
+        self.tally_by(player_tag, |_, me, _| me.character)
+    }
+
+    /// Wins/losses for `player_tag`, bucketed by stage ID.
+    pub fn get_stats_by_stage<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+    ) -> HashMap<u16, (usize, usize)> {
+        self.tally_by(player_tag, |replay, _, _| Some(replay.stage))
+    }
+
+    /// Wins/losses for `player_tag` against each opponent tag, leaving out opponents whose tag
+    /// couldn't be read ("Unknown").
+    pub fn head_to_head<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+    ) -> HashMap<String, (usize, usize)> {
+        self.tally_by(player_tag, |_, _, opponent| {
+            (opponent.name != "Unknown").then(|| opponent.name.clone())
+        })
+    }
+
+    /// Wins/losses for `player_tag` in each (their character, opponent's character) matchup.
+    pub fn matchup_stats<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+    ) -> HashMap<(u8, u8), (usize, usize)> {
+        self.tally_by(player_tag, |_, me, opponent| {
+            Some((me.character?, opponent.character?))
+        })
+    }
+
+    /// Characters `opponent_tag` played against `player_tag`, with the opponent's own
+    /// wins/losses on each.
+    pub fn opponent_character_stats<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        opponent_tag: &str,
+    ) -> HashMap<u8, (usize, usize)> {
+        let player_tag = player_tag.into();
+        self.tally_by(opponent_tag, |replay, them, me| {
+            let is_me = replay
+                .perspective(player_tag)
+                .is_some_and(|(player, _)| std::ptr::eq(player, me));
+            if is_me {
+                them.character
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Whether `replay` is at least [`Self::min_game_frames`] long. Games without a known
+    /// duration always are.
+    pub fn is_long_enough(&self, replay: &ReplayInfo) -> bool {
+        self.min_game_frames
+            .is_none_or(|min| replay.duration.is_none_or(|frames| frames >= min))
+    }
+
+    fn counts_in_breakdowns(&self, replay: &ReplayInfo) -> bool {
+        self.is_long_enough(replay)
+            && (!self.legal_stages_only || is_tournament_legal(replay.stage))
+    }
+
+    /// Counts wins/losses for `player_tag` under the key returned by `key`, which receives the
+    /// replay, the player and their opponent. Games without a winner are skipped.
+    fn tally_by<'a, K, F>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        key: F,
+    ) -> HashMap<K, (usize, usize)>
+    where
+        K: Eq + Hash,
+        F: Fn(&ReplayInfo, &PlayerInfo, &PlayerInfo) -> Option<K>,
+    {
+        let player_tag = player_tag.into();
+        let mut stats: HashMap<K, (usize, usize)> = HashMap::new();
+
+        for replay in self.replays.iter().filter(|r| self.counts_in_breakdowns(r)) {
+            let (Some((me, opponent)), Some(won)) =
+                (replay.perspective(player_tag), replay.won_by(player_tag))
+            else {
+                continue;
+            };
+            let Some(k) = key(replay, me, opponent) else {
+                continue;
+            };
+
+            let entry = stats.entry(k).or_default();
+            if won {
+                entry.0 += 1;
+            } else {
+                entry.1 += 1;
+            }
+        }
+
+        stats
+    }
+
+    /// Every loaded replay as a JSON array, with dates as ISO 8601 strings and ports as numbers.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.replays)
+    }
+
+    /// Write [`ReplayAnalyzer::to_json`] to `path`.
+    pub fn export_replays_json(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json()?)
+    }
+
+    /// Write the replays whose file path is in `selected` to `path`, formatted like
+    /// [`ReplayAnalyzer::to_json`].
+    pub fn export_selected_replays_json(
+        &self,
+        selected: &std::collections::HashSet<String>,
+        path: &Path,
+    ) -> io::Result<()> {
+        let replays: Vec<&ReplayInfo> = self
+            .replays
+            .iter()
+            .filter(|replay| selected.contains(&replay.file_path))
+            .collect();
+        fs::write(path, serde_json::to_string_pretty(&replays)?)
+    }
+
+    /// Write the per-character breakdown for `player_tag` as CSV.
+    pub fn export_character_stats_csv<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        path: &Path,
+    ) -> io::Result<()> {
+        let rows = self
+            .get_stats_by_character(player_tag)
+            .into_iter()
+            .map(|(character, record)| (character_id_to_name(character), record));
+        write_stats_csv(path, "character", rows)
+    }
+
+    /// Write the per-stage breakdown for `player_tag` as CSV.
+    pub fn export_stage_stats_csv<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        path: &Path,
+    ) -> io::Result<()> {
+        let rows = self
+            .get_stats_by_stage(player_tag)
+            .into_iter()
+            .map(|(stage, record)| (stage_id_to_name(stage), record));
+        write_stats_csv(path, "stage", rows)
+    }
+
+    /// Write the head-to-head record for `player_tag` against each opponent as CSV.
+    pub fn export_opponent_stats_csv<'a>(
+        &self,
+        player_tag: impl Into<PlayerTag<'a>>,
+        path: &Path,
+    ) -> io::Result<()> {
+        write_stats_csv(path, "opponent", self.head_to_head(player_tag))
+    }
+}
+
+impl Default for ReplayAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn parse_replay(file_path: &str) -> io::Result<ReplayInfo> {
+    let mut replay = parse_replay_from_reader(open_replay_file(Path::new(file_path))?)?;
+    replay.file_path = file_path.to_string();
+
+    // Prefer the recorded start time; the file date changes when replays are copied around
+    replay.modified = fs::metadata(file_path)
+        .ok()
+        .and_then(|metadata| metadata.modified().ok());
+    replay.date = replay.start_time.or(replay.modified); // Re-resolved against the chosen `DateSource` after scanning
+
+    Ok(replay)
+}
+
+/// Parse a replay from any seekable reader. There's no file, so `modified` is `None`.
+pub fn parse_replay_from_reader<R: io::Read + io::Seek>(r: R) -> io::Result<ReplayInfo> {
+    let game = read_game(r)?;
+
+    let players = extract_player_info(&game)?;
+    let result = determine_game_result(game.end.as_ref(), &players);
+    let stage = game.start.stage;
+    let stage_name = stage_id_to_name(stage);
+
+    // Extract duration from frame data
+    let duration = extract_game_duration(&game);
+
+    let start_time = extract_start_time(&game);
+
+    Ok(ReplayInfo {
+        file_path: String::new(),
+        players,
+        result,
+        end_method: GameEndMethod::from_end(game.end.as_ref()),
+        mode: GameMode::from_match(game.start.r#match.as_ref()),
+        stage,
+        stage_name,
+        duration,
+        format_version: Some(game.start.slippi.version.to_string()),
+        date: start_time,
+        start_time,
+        modified: None,
+        opponent_rank: None, // Will be filled in later by rank lookup
+        frames_analyzed: false,
+    })
+}
+
+/// Tidy a directory path as typed: trim surrounding whitespace, then expand `$VAR`, `${VAR}`
+/// and `%VAR%` environment variables and a leading `~`. Unset variables are left as written.
+pub fn expand_path(path: &str) -> String {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path.trim();
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        let sigil = &rest[start..=start];
+        let after = &rest[start + 1..];
+        let (name, remaining) = match (sigil, after.strip_prefix('{')) {
+            ("$", Some(braced)) => braced.split_once('}').unwrap_or(("", after)),
+            ("$", None) => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                after.split_at(end)
+            }
+            _ => after.split_once('%').unwrap_or(("", after)),
+        };
+        match std::env::var(name).ok().filter(|_| !name.is_empty()) {
+            Some(value) => {
+                expanded.push_str(&value);
+                rest = remaining;
+            }
+            None => {
+                expanded.push_str(sigil);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+
+    match (expanded.strip_prefix('~'), dirs::home_dir()) {
+        (Some(tail), Some(home)) if tail.is_empty() || tail.starts_with(['/', '\\']) => {
+            format!("{}{tail}", home.display())
+        }
+        _ => expanded,
+    }
+}
+
+/// The first of `dirs` that isn't an existing directory. Walking one finds nothing without
+/// an error, so this is checked before scanning.
+pub fn missing_dir(dirs: &[String]) -> Option<&String> {
+    dirs.iter().find(|dir| !Path::new(dir).is_dir())
+}
+
+/// Whether `path` looks like a replay: `.slp`, or `.slp.gz` as written by archival tools.
+pub fn is_replay_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+        return false;
+    };
+    name.ends_with(".slp") || name.ends_with(".slp.gz")
+}
+
+trait ReadSeek: io::Read + io::Seek {}
+
+impl<T: io::Read + io::Seek> ReadSeek for T {}
+
+/// Open a replay for reading, decompressing `.gz` files up front since parsing needs to seek.
+fn open_replay_file(path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+    let file = io::BufReader::new(fs::File::open(path)?);
+    if path.extension().and_then(|s| s.to_str()) == Some("gz") {
+        let mut bytes = Vec::new();
+        io::Read::read_to_end(&mut flate2::read::GzDecoder::new(file), &mut bytes)?;
+        Ok(Box::new(io::Cursor::new(bytes)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Oldest replay format eppi expects to read. Older files are still attempted, but a failure on
+/// one is reported as too old rather than corrupt. The newest is peppi's
+/// [`slippi::MAX_SUPPORTED_VERSION`].
+///
+/// 2.0.0 is the first format whose game end block says who quit out, and what every Slippi
+/// release since mid-2019 writes. Anything older comes from early builds eppi was never tested
+/// against.
+const MIN_SUPPORTED_VERSION: slippi::Version = slippi::Version(2, 0, 0);
+
+fn read_game<R: io::Read + io::Seek>(mut r: R) -> io::Result<Game> {
+    slippi::read(&mut r, None).map_err(|e| {
+        let message = match read_format_version(&mut r) {
+            Some(version) if version > slippi::MAX_SUPPORTED_VERSION => format!(
+                "Replay format {version} is newer than this version of eppi can read (up to {}). Update eppi to load it",
+                slippi::MAX_SUPPORTED_VERSION
+            ),
+            Some(version) if version < MIN_SUPPORTED_VERSION => format!(
+                "Replay format {version} is older than eppi supports ({MIN_SUPPORTED_VERSION} or newer): {e}"
+            ),
+            Some(version) => format!("Failed to parse replay (format {version}): {e}"),
+            None => format!("Failed to parse replay: {e}"),
+        };
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    })
+}
+
+/// The format version from a replay's Game Start event, read straight from the header so it's
+/// available even when the full parse fails.
+fn read_format_version<R: io::Read + io::Seek>(r: &mut R) -> Option<slippi::Version> {
+    // Signature, 4-byte raw length, then the Event Payloads event (0x35) and its size
+    const PAYLOADS_OFFSET: usize = slippi::FILE_SIGNATURE.len() + 4;
+    let mut header = [0u8; 512];
+    r.seek(io::SeekFrom::Start(0)).ok()?;
+    let len = r.read(&mut header).ok()?;
+    let header = &header[..len];
+    if !header.starts_with(&slippi::FILE_SIGNATURE) || header.get(PAYLOADS_OFFSET) != Some(&0x35) {
+        return None;
+    }
+    // The size byte counts itself, so Game Start (0x36) follows right after the payload sizes
+    let game_start = PAYLOADS_OFFSET + 1 + usize::from(*header.get(PAYLOADS_OFFSET + 1)?);
+    match header.get(game_start..game_start + 4)? {
+        [0x36, major, minor, build] => Some(slippi::Version(*major, *minor, *build)),
+        _ => None,
+    }
+}
+
+/// Frames where a new button went down, per minute of game time (60 frames a second).
+fn actions_per_minute(buttons: &[u16]) -> Option<f32> {
+    if buttons.is_empty() {
+        return None;
+    }
+    let actions = buttons.windows(2).filter(|w| w[1] & !w[0] != 0).count();
+    let minutes = buttons.len() as f32 / 3_600.0;
+    Some(actions as f32 / minutes)
+}
+
+/// Total percent taken over the game. Only increases count, so the drop back to 0% on
+/// respawn isn't treated as negative damage.
+fn damage_taken(percent: &[f32]) -> f32 {
+    percent.windows(2).map(|w| (w[1] - w[0]).max(0.0)).sum()
 }
 
-#[derive(Debug, Clone)]
-pub enum GameResult {
-    Player1Won,
-    Player2Won,
-    Unknown,
+/// Action states for landing lag after an aerial (`LandingAirN` through `LandingAirLw`).
+const LANDING_AIR_STATES: std::ops::RangeInclusive<u16> = 70..=74;
+
+/// Successful L-cancels and attempts, counted on the frame the player enters aerial landing lag.
+/// Slippi flags that frame with 1 for a successful L-cancel and 2 for a missed one.
+fn l_cancel_counts(states: &[u16], l_cancel: &[u8]) -> Option<(u32, u32)> {
+    let mut successful = 0;
+    let mut attempts = 0;
+    for (i, state) in states.iter().enumerate().skip(1) {
+        if !LANDING_AIR_STATES.contains(state) || LANDING_AIR_STATES.contains(&states[i - 1]) {
+            continue;
+        }
+        match l_cancel.get(i) {
+            Some(1) => successful += 1,
+            Some(2) => {}
+            _ => continue,
+        }
+        attempts += 1;
+    }
+    (attempts > 0).then_some((successful, attempts))
 }
 
-pub struct ReplayAnalyzer {
-    pub replays: Vec<ReplayInfo>,
-    pub rank_cache: HashMap<String, String>, // Cache for player tag -> rank
+/// Frames a player must spend out of hitstun and grabs, without taking damage, before the next
+/// hit on them counts as a new opening. Matches Slippi's own conversion detection.
+const NEUTRAL_RESET_FRAMES: u32 = 45;
+
+/// Action states for being hit (`DamageHi1` through `DamageFlyRoll`).
+const DAMAGE_STATES: std::ops::RangeInclusive<u16> = 75..=91;
+
+/// Action states for being held in a grab (`CapturePulledHi` through `CaptureFoot`).
+const GRABBED_STATES: std::ops::RangeInclusive<u16> = 223..=232;
+
+/// A hit that started a new conversion.
+struct Opening {
+    frame: usize,
+    attacker: Port,
 }
 
-impl ReplayAnalyzer {
-    pub fn new() -> Self {
-        Self {
-            replays: Vec::new(),
-            rank_cache: HashMap::new(),
+/// What [`walk_conversions`] found in one pass over the frames.
+struct Conversions {
+    /// Every opening in the game, in frame order.
+    openings: Vec<Opening>,
+    /// Frames where anyone lost a stock, in order.
+    stock_losses: Vec<usize>,
+}
+
+impl Conversions {
+    /// Who landed the first hit of each stock. Stocks run from the start of the game or the
+    /// last stock lost by anyone; the first hit of one is always an opening, since losing a
+    /// stock resets neutral. Stocks nobody was hit in aren't counted.
+    fn first_hits(&self) -> Vec<Port> {
+        let mut first_hits = Vec::new();
+        let mut current_stock = None;
+        for opening in &self.openings {
+            let stock = self
+                .stock_losses
+                .partition_point(|&frame| frame <= opening.frame);
+            if current_stock != Some(stock) {
+                current_stock = Some(stock);
+                first_hits.push(opening.attacker);
+            }
         }
+        first_hits
     }
+}
 
-    pub fn scan_directory(&mut self, dir_path: &str) -> io::Result<()> {
-        // Cache directory inside OS data dir (e.g. %APPDATA%/eppi)
-        let cache_dir = dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("eppi");
-        let cache_path = cache_dir.join("bad_replays.txt");
+/// Find every opening and stock loss in the game.
+///
+/// Neutral-reset heuristic: a player is "being punished" from the first frame they take damage
+/// until they've spent [`NEUTRAL_RESET_FRAMES`] frames in a row not taking damage, not in a
+/// damage state and not grabbed, or until they lose a stock. A hit landing while they aren't
+/// being punished is an opening for whoever landed it: the victim's `last_hit_by`, or the only
+/// other player when that's missing. Hits that can't be pinned on another player (e.g. in
+/// doubles without `last_hit_by`) aren't counted.
+fn walk_conversions(ports: &[PortData]) -> Conversions {
+    let mut openings = Vec::new();
+    let mut stock_losses = Vec::new();
+    for victim in ports {
+        let post = &victim.leader.post;
+        let percent = post.percent.values();
+        let states = post.state.values();
+        let stocks = post.stocks.values();
+        let last_hit_by = post.last_hit_by.values();
+        let only_opponent = match ports {
+            [a, b] => Some(if a.port == victim.port {
+                b.port
+            } else {
+                a.port
+            }),
+            _ => None,
+        };
 
-        // Load bad-file cache if it exists
-        let mut bad_cache: std::collections::HashSet<String> =
-            if let Ok(contents) = fs::read_to_string(&cache_path) {
-                contents
-                    .lines()
-                    .map(|l| l.trim())
-                    .filter(|l| !l.is_empty())
-                    .map(|l| l.to_owned())
-                    .collect()
+        // Start in neutral, so the first hit of the game is an opening
+        let mut calm_frames = NEUTRAL_RESET_FRAMES;
+        for frame in 1..percent.len() {
+            if stocks[frame] < stocks[frame - 1] {
+                calm_frames = NEUTRAL_RESET_FRAMES;
+                stock_losses.push(frame);
+                continue;
+            }
+            let hit = percent[frame] > percent[frame - 1];
+            if hit && calm_frames >= NEUTRAL_RESET_FRAMES {
+                let attacker = Port::try_from(last_hit_by[frame])
+                    .ok()
+                    .filter(|&port| port != victim.port)
+                    .or(only_opponent);
+                if let Some(attacker) = attacker {
+                    openings.push(Opening { frame, attacker });
+                }
+            }
+            let punished = hit
+                || DAMAGE_STATES.contains(&states[frame])
+                || GRABBED_STATES.contains(&states[frame]);
+            calm_frames = if punished {
+                0
             } else {
-                std::collections::HashSet::new()
+                calm_frames.saturating_add(1)
             };
+        }
+    }
+    openings.sort_by_key(|opening| opening.frame);
+    stock_losses.sort_unstable();
+    Conversions {
+        openings,
+        stock_losses,
+    }
+}
 
-        // Install a silent panic hook once to suppress per-file panic prints
-        static HOOK_SET: std::sync::Once = std::sync::Once::new();
-        HOOK_SET.call_once(|| {
-            let _ = panic::take_hook(); // drop the default that prints
-            panic::set_hook(Box::new(|_| {}));
-        });
+/// Cache directory inside OS data dir (e.g. %APPDATA%/eppi)
+pub fn cache_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("eppi")
+}
 
-        // First, collect all .slp files, skipping those known to be bad
-        let slp_files: Vec<_> = WalkDir::new(dir_path)
-            .into_iter()
-            .filter_map(|e| {
-                if let Ok(entry) = e {
-                    if entry.path().is_file()
-                        && entry.path().extension().and_then(|s| s.to_str()) == Some("slp")
-                        && !bad_cache.contains(entry.path().to_string_lossy().as_ref())
-                    {
-                        Some(entry.path().to_path_buf())
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect();
+/// Paths of replays that failed to parse, skipped on later scans.
+fn bad_replay_cache_path() -> PathBuf {
+    cache_dir().join("bad_replays.txt")
+}
 
-        log::info!("Found {} .slp files to process", slp_files.len());
+/// The paths in [`bad_replay_cache_path`], or none if it doesn't exist.
+fn load_bad_replay_cache() -> std::collections::HashSet<String> {
+    match fs::read_to_string(bad_replay_cache_path()) {
+        Ok(contents) => contents
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_owned())
+            .collect(),
+        Err(_) => std::collections::HashSet::new(),
+    }
+}
 
-        // Build a rayon pool with physical core count to avoid hyper-thread oversubscription
-        let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(num_cpus::get_physical())
-            .build()
-            .map_err(|e| io::Error::other(format!("Thread-pool error: {e}")))?;
+/// Ranks looked up on slippi.gg, kept across sessions.
+pub fn rank_cache_path() -> PathBuf {
+    cache_dir().join("rank_cache.json")
+}
 
-        let new_bad: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// Parsed replays keyed by path, size and modified time, so unchanged files aren't re-parsed.
+fn parse_cache_path() -> PathBuf {
+    cache_dir().join("parse_cache.json")
+}
 
-        let mut replays: Vec<ReplayInfo> = pool.install(|| {
-            slp_files
-                .into_par_iter()
-                .filter_map(|path| {
-                    let file_path = path.to_str()?.to_string();
+/// Replays from the last scan, see [`ReplayAnalyzer::save_replay_list`].
+fn replay_list_path() -> PathBuf {
+    cache_dir().join("replays.json")
+}
 
-                    // Use catch_unwind to handle panics from corrupt replay files
-                    let result = panic::catch_unwind(|| parse_replay(&file_path));
+/// Read a JSON cache file. A missing or corrupt file gives an empty cache.
+fn load_json_cache<T: serde::de::DeserializeOwned + Default>(path: &Path) -> T {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring corrupt cache at {path:?}: {e}");
+            T::default()
+        }),
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                log::warn!("Failed to read cache at {path:?}: {e}");
+            }
+            T::default()
+        }
+    }
+}
 
-                    match result {
-                        Ok(Ok(replay_info)) => Some(replay_info),
-                        _ => {
-                            if let Ok(mut vec) = new_bad.lock() {
-                                vec.push(file_path.clone());
-                            }
-                            None
-                        }
-                    }
-                })
-                .collect()
-        });
+fn save_json_cache<T: serde::Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string(value)?;
+    fs::write(path, json)
+}
 
-        let skipped_count = new_bad.lock().map(|v| v.len()).unwrap_or(0);
-        log::info!(
-            "Successfully parsed {} replays (skipped {skipped_count})",
-            replays.len()
-        );
+/// Delete the parse cache so the next scan re-parses and retries every replay. Returns how
+/// many entries were removed.
+pub fn clear_parse_cache() -> io::Result<usize> {
+    let mut removed = 0;
 
-        // Sort by date (newest first) in parallel
-        replays.par_sort_unstable_by(|a, b| {
-            match (a.date, b.date) {
-                (Some(date_a), Some(date_b)) => date_b.cmp(&date_a), // Newer first
-                (Some(_), None) => std::cmp::Ordering::Less,         // Files with dates come first
-                (None, Some(_)) => std::cmp::Ordering::Greater, // Files without dates come last
-                (None, None) => std::cmp::Ordering::Equal,      // Equal if both have no date
-            }
-        });
+    let path = bad_replay_cache_path();
+    match fs::read_to_string(&path) {
+        Ok(contents) => {
+            removed += contents.lines().filter(|l| !l.trim().is_empty()).count();
+            fs::remove_file(&path)?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
 
-        self.replays = replays;
+    let path = parse_cache_path();
+    if path.exists() {
+        let parsed: HashMap<String, ParseCacheEntry> = load_json_cache(&path);
+        removed += parsed.len();
+        fs::remove_file(&path)?;
+    }
 
-        let new_bad_vec = new_bad.into_inner().unwrap_or_default();
+    log::info!("Cleared {removed} entries from the parse cache");
+    Ok(removed)
+}
 
-        if !new_bad_vec.is_empty() {
-            // Ensure cache dir exists
-            if let Err(e) = fs::create_dir_all(&cache_dir) {
-                log::error!("Failed to create cache directory {cache_dir:?}: {e}");
-            }
-            for p in new_bad_vec {
-                bad_cache.insert(p);
-            }
-            if let Some(parent) = cache_path.parent() {
-                if !parent.exists() {
-                    log::warn!("Parent directory {parent:?} does NOT exist – creating it");
-                    if let Err(e) = fs::create_dir_all(parent) {
-                        log::error!("Failed to create parent directory {parent:?}: {e}");
-                    }
-                }
-            }
-            let data = bad_cache.into_iter().collect::<Vec<_>>().join("\n");
-            log::info!("Caching {skipped_count} bad replay paths to {cache_path:?}");
-            if let Err(e) = fs::write(&cache_path, data) {
-                log::error!("Failed to update bad replay cache at {cache_path:?}: {e}");
+/// Replay files under `dirs`, walked per `options`. A file under more than one of the
+/// directories (e.g. nested ones) is only listed once. On slow (network) drives this is the
+/// slow part of a scan, so it reports [`ScanProgress::Enumerating`] as it goes and stops with
+/// [`scan_cancelled`] once `cancel` is set.
+fn find_replay_files(
+    dirs: &[String],
+    options: &ScanOptions,
+    cancel: &AtomicBool,
+    progress: &(impl Fn(ScanProgress) + Sync),
+) -> io::Result<ReplayFiles> {
+    let mut files = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut walk_errors = Vec::new();
+    let entries = dirs.iter().flat_map(|dir| options.walk(dir));
+    for (visited, e) in entries.enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(scan_cancelled());
+        }
+        if visited % ENUMERATION_HEARTBEAT == 0 {
+            progress(ScanProgress::Enumerating { found: files.len() });
+        }
+        let entry = match e {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Couldn't read while scanning: {e}");
+                walk_errors.push(walk_error(&e));
+                continue;
             }
+        };
+        if entry.path().is_file()
+            && is_replay_file(entry.path())
+            && seen.insert(entry.path().to_path_buf())
+        {
+            files.push(entry.into_path());
         }
-
-        Ok(())
     }
+    Ok(ReplayFiles { files, walk_errors })
+}
 
-    pub fn get_stats_for_player(&self, player_tag: &str) -> (usize, usize) {
-        let mut wins = 0;
-        let mut losses = 0;
+/// What [`ReplayAnalyzer::find_new_replays`] found.
+#[derive(Debug, Default)]
+pub struct NewReplays {
+    /// Replays that weren't loaded yet.
+    pub replays: Vec<ReplayInfo>,
+    /// `(path, error)` for every entry that couldn't be read.
+    pub walk_errors: Vec<(String, String)>,
+}
+
+/// What [`find_replay_files`] found.
+struct ReplayFiles {
+    files: Vec<PathBuf>,
+    /// `(path, error)` for every entry that couldn't be read.
+    walk_errors: Vec<(String, String)>,
+}
+
+/// What [`parse_replay_files`] made of a batch of files.
+struct ParsedFiles {
+    /// Parse cache entries for the files that parsed, keyed by path.
+    parsed: Vec<(String, ParseCacheEntry)>,
+    /// `(path, error)` for files that failed to parse or crashed the parser.
+    failed: Vec<(String, String)>,
+    /// How many of `parsed` weren't in the parse cache.
+    reparsed: usize,
+}
+
+/// Parse `files` on a pool of one thread per physical core, reusing `parse_cache` entries for
+/// files that haven't changed since. Parser panics on corrupt files are caught and count as
+/// failures. Reports [`ScanProgress::Parsing`] and hands each replay to `on_replay` as it's
+/// parsed; once `cancel` is set, the files not started yet are skipped.
+fn parse_replay_files(
+    files: Vec<PathBuf>,
+    parse_cache: &HashMap<String, ParseCacheEntry>,
+    cancel: &AtomicBool,
+    progress: &(impl Fn(ScanProgress) + Sync),
+    on_replay: &(impl Fn(&ReplayInfo) + Sync),
+) -> io::Result<ParsedFiles> {
+    // Install a silent panic hook once to suppress per-file panic prints
+    static HOOK_SET: std::sync::Once = std::sync::Once::new();
+    HOOK_SET.call_once(|| {
+        let _ = panic::take_hook(); // drop the default that prints
+        panic::set_hook(Box::new(|_| {}));
+    });
 
-        for replay in &self.replays {
-            if replay.player1.name == player_tag {
-                match replay.result {
-                    GameResult::Player1Won => wins += 1,
-                    GameResult::Player2Won => losses += 1,
-                    GameResult::Unknown => {}
+    let total = files.len();
+    progress(ScanProgress::Parsing { done: 0, total });
+    let done = AtomicUsize::new(0);
+    let reparsed = AtomicUsize::new(0);
+
+    // Build a rayon pool with physical core count to avoid hyper-thread oversubscription
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_cpus::get_physical())
+        .build()
+        .map_err(|e| io::Error::other(format!("Thread-pool error: {e}")))?;
+
+    let failed: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+    let parsed: Vec<(String, ParseCacheEntry)> = pool.install(|| {
+        files
+            .into_par_iter()
+            .filter_map(|path| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
                 }
-            } else if replay.player2.name == player_tag {
-                match replay.result {
-                    GameResult::Player1Won => losses += 1,
-                    GameResult::Player2Won => wins += 1,
-                    GameResult::Unknown => {}
+                let file_path = path.to_str()?.to_string();
+                let metadata = fs::metadata(&path).ok();
+                let size = metadata.as_ref().map_or(0, |m| m.len());
+                let modified = metadata.and_then(|m| m.modified().ok());
+
+                // Reuse the cached parse if the file hasn't changed since
+                let cached = parse_cache
+                    .get(&file_path)
+                    .filter(|entry| {
+                        entry.format == PARSE_CACHE_FORMAT
+                            && entry.size == size
+                            && entry.modified == modified
+                    })
+                    .map(|entry| entry.replay.clone());
+                let result = match cached {
+                    Some(replay_info) => Ok(Ok(replay_info)),
+                    None => {
+                        reparsed.fetch_add(1, Ordering::Relaxed);
+                        // Use catch_unwind to handle panics from corrupt replay files
+                        panic::catch_unwind(|| parse_replay(&file_path))
+                    }
+                };
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                progress(ScanProgress::Parsing { done, total });
+
+                match result {
+                    Ok(Ok(replay_info)) => {
+                        on_replay(&replay_info);
+                        let entry = ParseCacheEntry {
+                            format: PARSE_CACHE_FORMAT,
+                            size,
+                            modified,
+                            replay: replay_info,
+                        };
+                        Some((file_path, entry))
+                    }
+                    Ok(Err(e)) => {
+                        if let Ok(mut vec) = failed.lock() {
+                            vec.push((file_path, e.to_string()));
+                        }
+                        None
+                    }
+                    Err(panic) => {
+                        let message = panic
+                            .downcast_ref::<&str>()
+                            .map(|s| s.to_string())
+                            .or_else(|| panic.downcast_ref::<String>().cloned())
+                            .unwrap_or_else(|| "unknown error".to_string());
+                        if let Ok(mut vec) = failed.lock() {
+                            vec.push((file_path, format!("Parser crashed: {message}")));
+                        }
+                        None
+                    }
                 }
-            }
-        }
+            })
+            .collect()
+    });
 
-        (wins, losses)
+    Ok(ParsedFiles {
+        parsed,
+        failed: failed.into_inner().unwrap_or_default(),
+        reparsed: reparsed.into_inner(),
+    })
+}
+
+fn scan_cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Scan cancelled")
+}
+
+/// Writes `key,games,wins,losses,win_rate` rows, most-played first.
+fn write_stats_csv(
+    path: &Path,
+    key_header: &str,
+    rows: impl IntoIterator<Item = (String, (usize, usize))>,
+) -> io::Result<()> {
+    let mut rows: Vec<_> = rows.into_iter().collect();
+    rows.sort_by(|(a_key, (a_w, a_l)), (b_key, (b_w, b_l))| {
+        (b_w + b_l).cmp(&(a_w + a_l)).then_with(|| a_key.cmp(b_key))
+    });
+
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    writeln!(out, "{key_header},games,wins,losses,win_rate")?;
+    for (key, (wins, losses)) in rows {
+        let games = wins + losses;
+        let win_rate = if games > 0 {
+            wins as f64 / games as f64 * 100.0
+        } else {
+            0.0
+        };
+        writeln!(
+            out,
+            "{},{games},{wins},{losses},{win_rate:.1}",
+            csv_field(&key)
+        )?;
+    }
+    out.flush()
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or newline.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
     }
+}
 
-    pub fn get_cached_rank(&self, player_tag: &str) -> Option<&String> {
-        self.rank_cache.get(player_tag)
+/// Match start time from the `startAt` metadata key, an ISO-8601 UTC timestamp.
+fn extract_start_time(game: &Game) -> Option<SystemTime> {
+    let start_at = game.metadata.as_ref()?.get("startAt")?.as_str()?;
+    match chrono::DateTime::parse_from_rfc3339(start_at) {
+        Ok(date) => Some(date.into()),
+        // Some older replays omit the UTC offset
+        Err(_) => chrono::NaiveDateTime::parse_from_str(start_at, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .map(|date| date.and_utc().into()),
     }
 }
 
-impl Default for ReplayAnalyzer {
-    fn default() -> Self {
-        Self::new()
+/// Frame ID of the first frame players can act on. Replays start at frame -123 with the
+/// "Ready" countdown, and the in-game timer starts with "GO" at -39.
+const FIRST_PLAYABLE_FRAME: i32 = -39;
+
+/// Game length in frames as the in-game timer counts it, leaving out the "Ready" countdown
+/// and, for games won on stocks, the "GAME!" frames after the final KO.
+fn extract_game_duration(game: &Game) -> Option<i32> {
+    // Metadata records the last frame even when frame data was skipped or is incomplete
+    let mut last_frame = game
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get("lastFrame"))
+        .and_then(|frame| frame.as_i64())
+        .and_then(|frame| i32::try_from(frame).ok())
+        .or_else(|| game.frames.id.iter().next_back().flatten().copied())?;
+    // The timer stops on the final KO. Timeouts and quit-outs have no final KO to trim to.
+    if GameEndMethod::from_end(game.end.as_ref()) == GameEndMethod::Stocks {
+        if let Some(ko_frame) = last_stock_loss(&game.frames.ports)
+            .and_then(|index| game.frames.id.values().get(index).copied())
+        {
+            last_frame = last_frame.min(ko_frame);
+        }
     }
+    Some(playable_frames(last_frame))
 }
 
-pub fn parse_replay(file_path: &str) -> io::Result<ReplayInfo> {
-    let mut r = io::BufReader::new(fs::File::open(file_path)?);
-    let game = slippi::read(&mut r, None).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Failed to parse replay: {e}"),
-        )
-    })?;
+/// Index of the last frame where anyone lost a stock.
+fn last_stock_loss(ports: &[PortData]) -> Option<usize> {
+    ports
+        .iter()
+        .filter_map(|port| {
+            let stocks = port.leader.post.stocks.values();
+            (1..stocks.len())
+                .rev()
+                .find(|&frame| stocks[frame] < stocks[frame - 1])
+        })
+        .max()
+}
 
-    let (player1, player2) = extract_player_info(&game)?;
-    let result = determine_game_result(&game)?;
-    let stage = game.start.stage;
-    let stage_name = stage_id_to_name(stage);
+/// Frames from "GO" up to and including `last_frame`. Games quit out of during the countdown
+/// have none.
+fn playable_frames(last_frame: i32) -> i32 {
+    (last_frame - FIRST_PLAYABLE_FRAME + 1).max(0)
+}
 
-    // Extract duration from frame data
-    let duration = extract_game_duration(&game);
+/// Canonical form of a connect code: trimmed and uppercase, with the full-width `＃` the game
+/// writes turned into `#`, so `bean＃888 ` and `BEAN#888` are the same player.
+pub fn normalize_tag(tag: &str) -> String {
+    tag_chars(tag).collect()
+}
 
-    // Get file modification date
-    let date = fs::metadata(file_path)
-        .ok()
-        .and_then(|metadata| metadata.modified().ok());
+/// Whether two tags are the same once normalized (see [`normalize_tag`]), without allocating.
+pub fn same_tag(a: &str, b: &str) -> bool {
+    tag_chars(a).eq(tag_chars(b))
+}
 
-    Ok(ReplayInfo {
-        player1,
-        player2,
-        result,
-        stage_name,
-        duration,
-        date,
-        opponent_rank: None, // Will be filled in later by rank lookup
+fn tag_chars(tag: &str) -> impl Iterator<Item = char> + '_ {
+    tag.trim().chars().map(|c| match c {
+        '＃' => '#',
+        c => c.to_ascii_uppercase(),
     })
 }
 
-fn extract_game_duration(game: &Game) -> Option<i32> {
-    // Get the last frame ID which represents the game duration in frames
-    if let Some(last_frame) = game.frames.id.iter().enumerate().next_back() {
-        if let Some(frame_id) = last_frame.1 {
-            return Some(*frame_id);
-        }
+/// Local midnight at the start of today.
+pub fn start_of_today() -> SystemTime {
+    let now = chrono::Local::now();
+    now.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(chrono::Local).earliest())
+        // A DST change skipped midnight; count from an hour later
+        .or_else(|| {
+            let one_am = now.date_naive().and_hms_opt(1, 0, 0)?;
+            one_am.and_local_timezone(chrono::Local).earliest()
+        })
+        .map_or_else(SystemTime::now, SystemTime::from)
+}
+
+pub fn character_id_to_name(character_id: u8) -> String {
+    match character_id {
+        0 => "Captain Falcon".to_string(),
+        1 => "Donkey Kong".to_string(),
+        2 => "Fox".to_string(),
+        3 => "Mr. Game & Watch".to_string(),
+        4 => "Kirby".to_string(),
+        5 => "Bowser".to_string(),
+        6 => "Link".to_string(),
+        7 => "Luigi".to_string(),
+        8 => "Mario".to_string(),
+        9 => "Marth".to_string(),
+        10 => "Mewtwo".to_string(),
+        11 => "Ness".to_string(),
+        12 => "Peach".to_string(),
+        13 => "Pikachu".to_string(),
+        14 => "Ice Climbers".to_string(),
+        15 => "Jigglypuff".to_string(),
+        16 => "Samus".to_string(),
+        17 => "Yoshi".to_string(),
+        18 => "Zelda".to_string(),
+        19 => "Sheik".to_string(),
+        20 => "Falco".to_string(),
+        21 => "Young Link".to_string(),
+        22 => "Dr. Mario".to_string(),
+        23 => "Roy".to_string(),
+        24 => "Pichu".to_string(),
+        25 => "Ganondorf".to_string(),
+        // Master Hand, the Wireframes, Giga Bowser, Crazy Hand, Sandbag, ...
+        _ => format!("Unknown Character ({character_id})"),
     }
-    None
 }
 
-fn stage_id_to_name(stage_id: u16) -> String {
+pub fn stage_id_to_name(stage_id: u16) -> String {
     match stage_id {
         2 => "Fountain of Dreams".to_string(),
         3 => "Pokémon Stadium".to_string(),
@@ -288,25 +2195,76 @@ fn stage_id_to_name(stage_id: u16) -> String {
     }
 }
 
-fn extract_player_info(game: &Game) -> io::Result<(PlayerInfo, PlayerInfo)> {
-    // Handle both cases: with and without metadata
-    let (player1_name, player2_name) = if let Some(metadata) = &game.metadata {
-        extract_names_from_metadata(metadata)
-    } else {
-        ("Unknown".to_string(), "Unknown".to_string())
-    };
+/// Name of a teams-mode team color, as stored in [`PlayerInfo::team`].
+pub fn team_name(team: u8) -> Option<&'static str> {
+    match team {
+        0 => Some("Red Team"),
+        1 => Some("Blue Team"),
+        2 => Some("Green Team"),
+        _ => None,
+    }
+}
 
-    // Get character and team info from start data
-    let mut players_info = Vec::new();
+/// Whether the stage is on the usual singles legal list: Fountain of Dreams, Pokémon Stadium,
+/// Yoshi's Story, Dream Land, Battlefield and Final Destination.
+pub fn is_tournament_legal(stage_id: u16) -> bool {
+    matches!(stage_id, 2 | 3 | 8 | 28 | 31 | 32)
+}
 
-    for (i, _player) in game.start.players.iter().enumerate() {
-        let name = if i == 0 { &player1_name } else { &player2_name };
+fn extract_player_info(game: &Game) -> io::Result<Vec<PlayerInfo>> {
+    // Stock counts are only meaningful if the game was played out
+    let quit_out = game
+        .end
+        .as_ref()
+        .is_none_or(|end| end.method == EndMethod::NoContest);
 
-        players_info.push(PlayerInfo { name: name.clone() });
-    }
+    // Get character and team info from start data, and names from metadata (keyed by port)
+    let players_info: Vec<PlayerInfo> = game
+        .start
+        .players
+        .iter()
+        .map(|player| {
+            let metadata = game.metadata.as_ref();
+            let name = metadata
+                .and_then(|metadata| extract_name_from_metadata(metadata, player.port, "code"))
+                .unwrap_or_else(|| "Unknown".to_string());
+            // Newer replays also have it in the game start block
+            let display_name = metadata
+                .and_then(|metadata| extract_name_from_metadata(metadata, player.port, "netplay"))
+                .or_else(|| {
+                    let netplay = player.netplay.as_ref()?;
+                    Some(netplay.name.as_str().to_string())
+                })
+                .filter(|display_name| !display_name.is_empty());
+            let name_tag = player
+                .name_tag
+                .as_ref()
+                .map(|tag| tag.as_str().trim().to_string())
+                .filter(|tag| !tag.is_empty());
+            PlayerInfo {
+                name,
+                display_name,
+                name_tag,
+                character: Some(player.character),
+                character_name: character_id_to_name(player.character),
+                port: player.port,
+                team: player.team.map(|team| team.color),
+                final_stocks: if quit_out {
+                    None
+                } else {
+                    extract_final_stocks(game, player.port)
+                },
+                apm: None,
+                l_cancels: None,
+                damage_dealt: None,
+                openings: None,
+                first_hits: None,
+            }
+        })
+        .collect();
 
     if players_info.len() >= 2 {
-        Ok((players_info[0].clone(), players_info[1].clone()))
+        Ok(players_info)
     } else {
         Err(io::Error::new(
             io::ErrorKind::InvalidData,
@@ -315,50 +2273,418 @@ fn extract_player_info(game: &Game) -> io::Result<(PlayerInfo, PlayerInfo)> {
     }
 }
 
-fn extract_names_from_metadata(
+/// Stocks `port` had on the last frame.
+fn extract_final_stocks(game: &Game, port: Port) -> Option<u8> {
+    let port_data = game.frames.ports.iter().find(|p| p.port == port)?;
+    port_data.leader.post.stocks.values().last().copied()
+}
+
+/// One of the `names` of the player on `port`: `code` for the connect code or `netplay` for the
+/// display name.
+fn extract_name_from_metadata(
     metadata: &serde_json::Map<String, serde_json::Value>,
-) -> (String, String) {
-    if let Some(players) = metadata.get("players").and_then(|p| p.as_object()) {
-        let player1_name = players
-            .get("0")
-            .and_then(|p| p.as_object())
-            .and_then(|p| p.get("names"))
-            .and_then(|n| n.as_object())
-            .and_then(|n| n.get("code"))
-            .and_then(|c| c.as_str())
-            .unwrap_or("Unknown")
-            .to_string();
-
-        let player2_name = players
-            .get("1")
-            .and_then(|p| p.as_object())
-            .and_then(|p| p.get("names"))
-            .and_then(|n| n.as_object())
-            .and_then(|n| n.get("code"))
-            .and_then(|c| c.as_str())
-            .unwrap_or("Unknown")
-            .to_string();
-
-        (player1_name, player2_name)
+    port: Port,
+    key: &str,
+) -> Option<String> {
+    metadata
+        .get("players")
+        .and_then(|p| p.as_object())
+        .and_then(|players| players.get(&(port as u8).to_string()))
+        .and_then(|p| p.as_object())
+        .and_then(|p| p.get("names"))
+        .and_then(|n| n.as_object())
+        .and_then(|n| n.get(key))
+        .and_then(|c| c.as_str())
+        .map(str::to_string)
+}
+
+/// Which side `players[index]` is on: 0 for the side the first player is on ("Team 1"), 1
+/// otherwise. In singles that's just the player's slot.
+fn side_of(players: &[PlayerInfo], index: usize) -> usize {
+    let player1_team = players[0].team;
+    if index == 0 || (player1_team.is_some() && players[index].team == player1_team) {
+        0
     } else {
-        ("Unknown".to_string(), "Unknown".to_string())
+        1
     }
 }
 
-fn determine_game_result(game: &Game) -> io::Result<GameResult> {
-    if let Some(end) = &game.end {
-        if let Some(players) = &end.players {
-            // Find the winner (placement == 0)
-            for player in players {
-                if player.placement == 0 {
-                    return Ok(match player.port {
-                        Port::P1 | Port::P3 => GameResult::Player1Won, // Assuming P1/P3 are team 1
-                        Port::P2 | Port::P4 => GameResult::Player2Won, // Assuming P2/P4 are team 2
-                    });
-                }
-            }
+/// Which side won, from the placements in the game end block. Winners (placement 0) on both
+/// sides means a draw.
+fn determine_game_result(end: Option<&peppi::game::End>, players: &[PlayerInfo]) -> GameResult {
+    let Some(end) = end else {
+        return GameResult::Unknown;
+    };
+    if end.method == EndMethod::NoContest {
+        return GameResult::NoContest;
+    }
+    let mut won = [false; 2];
+    for winner in end.players.iter().flatten().filter(|p| p.placement == 0) {
+        if let Some(index) = players.iter().position(|p| p.port == winner.port) {
+            won[side_of(players, index)] = true;
+        }
+    }
+    match won {
+        [true, false] => GameResult::Player1Won,
+        [false, true] => GameResult::Player2Won,
+        [true, true] => GameResult::Draw,
+        [false, false] => GameResult::Unknown,
+    }
+}
+
+/// Serde helpers for `Option<SystemTime>` as an ISO 8601 string, e.g. `2025-03-14T21:05:00Z`.
+pub(crate) mod iso_8601 {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn format(time: SystemTime) -> String {
+        chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()
+    }
+
+    pub fn serialize<S: Serializer>(time: &Option<SystemTime>, s: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => s.serialize_some(&format(*time)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SystemTime>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|text| {
+                chrono::DateTime::parse_from_rfc3339(&text)
+                    .map(SystemTime::from)
+                    .map_err(serde::de::Error::custom)
+            })
+            .transpose()
+    }
+}
+
+/// Serde helpers for [`Port`] as its number, 1 to 4.
+mod port_number {
+    use peppi::game::Port;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(port: &Port, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u8(*port as u8 + 1)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Port, D::Error> {
+        let number = u8::deserialize(d)?;
+        number
+            .checked_sub(1)
+            .and_then(|index| Port::try_from(index).ok())
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid port {number}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::helpers::format_duration;
+    use peppi::game::{Bytes, End, PlayerEnd};
+
+    #[test]
+    fn playable_frames_skip_the_ready_countdown() {
+        // Quit out on the first frame of "Ready"
+        assert_eq!(playable_frames(-123), 0);
+        // "GO" is the first frame the timer counts
+        assert_eq!(playable_frames(FIRST_PLAYABLE_FRAME), 1);
+        assert_eq!(playable_frames(0), 40);
+    }
+
+    #[test]
+    fn playable_frames_match_the_in_game_timer() {
+        // A game that ends as the timer reads 2:30 elapsed
+        let last_frame = FIRST_PLAYABLE_FRAME + 150 * 60 - 1;
+        assert_eq!(playable_frames(last_frame), 9_000);
+        assert_eq!(format_duration(playable_frames(last_frame)), "2:30");
+        // A full 8 minute timeout
+        let last_frame = FIRST_PLAYABLE_FRAME + 8 * 60 * 60 - 1;
+        assert_eq!(format_duration(playable_frames(last_frame)), "8:00");
+        assert_eq!(format_duration(playable_frames(-123)), "0:00");
+    }
+
+    fn player(port: Port, team: Option<u8>) -> PlayerInfo {
+        PlayerInfo {
+            name: "Unknown".to_string(),
+            display_name: None,
+            name_tag: None,
+            character: None,
+            character_name: String::new(),
+            port,
+            team,
+            final_stocks: None,
+            apm: None,
+            l_cancels: None,
+            damage_dealt: None,
+            openings: None,
+            first_hits: None,
+        }
+    }
+
+    /// A game won on stocks, placing the `winners` first and everyone else second.
+    fn end_block(players: &[PlayerInfo], winners: &[Port]) -> End {
+        End {
+            method: EndMethod::Game,
+            bytes: Bytes(Vec::new()),
+            lras_initiator: None,
+            players: Some(
+                players
+                    .iter()
+                    .map(|p| PlayerEnd {
+                        port: p.port,
+                        placement: if winners.contains(&p.port) { 0 } else { 1 },
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn result(players: &[PlayerInfo], winners: &[Port]) -> GameResult {
+        determine_game_result(Some(&end_block(players, winners)), players)
+    }
+
+    #[test]
+    fn singles_winner_is_their_slot_not_their_port_parity() {
+        // Splitting by port parity (P1/P3 against P2/P4) put these two on the same team
+        let players = [player(Port::P2, None), player(Port::P4, None)];
+        assert_eq!(result(&players, &[Port::P2]), GameResult::Player1Won);
+        assert_eq!(result(&players, &[Port::P4]), GameResult::Player2Won);
+
+        let players = [player(Port::P1, None), player(Port::P3, None)];
+        assert_eq!(result(&players, &[Port::P3]), GameResult::Player2Won);
+    }
+
+    #[test]
+    fn teams_winners_are_grouped_by_team() {
+        // P1 and P2 on team 0 against P3 and P4 on team 1
+        let players = [
+            player(Port::P1, Some(0)),
+            player(Port::P2, Some(0)),
+            player(Port::P3, Some(1)),
+            player(Port::P4, Some(1)),
+        ];
+        assert_eq!(
+            result(&players, &[Port::P1, Port::P2]),
+            GameResult::Player1Won
+        );
+        // The winner is on P2 but sits on player1's team
+        assert_eq!(result(&players, &[Port::P2]), GameResult::Player1Won);
+        assert_eq!(
+            result(&players, &[Port::P3, Port::P4]),
+            GameResult::Player2Won
+        );
+        assert_eq!(result(&players, &[Port::P2, Port::P3]), GameResult::Draw);
+    }
+
+    #[test]
+    fn unfinished_games_have_no_winner() {
+        let players = [player(Port::P1, None), player(Port::P2, None)];
+        assert_eq!(determine_game_result(None, &players), GameResult::Unknown);
+        assert_eq!(result(&players, &[]), GameResult::Unknown);
+        let mut quit = end_block(&players, &[Port::P1]);
+        quit.method = EndMethod::NoContest;
+        assert_eq!(
+            determine_game_result(Some(&quit), &players),
+            GameResult::NoContest
+        );
+    }
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn gzipped_replays_parse_like_plain_ones() {
+        let plain = parse_replay(&fixture("game.slp")).unwrap();
+        let gzipped = parse_replay(&fixture("game.slp.gz")).unwrap();
+
+        assert_eq!(plain.players[0].name, "ALCE#123");
+        assert_eq!(plain.players[1].display_name.as_deref(), Some("Bob"));
+        assert_eq!(plain.result, GameResult::Player1Won);
+        assert_eq!(plain.stage_name, stage_id_to_name(31));
+        assert_eq!(plain.duration, Some(playable_frames(8_960)));
+        assert_eq!(plain.format_version.as_deref(), Some("3.13.0"));
+
+        // Everything but where the file lives should match
+        let comparable = |replay: &ReplayInfo| {
+            let mut value = serde_json::to_value(replay).unwrap();
+            value["file_path"] = serde_json::Value::Null;
+            value["modified"] = serde_json::Value::Null;
+            value
+        };
+        assert_eq!(comparable(&plain), comparable(&gzipped));
+    }
+
+    #[test]
+    fn duration_falls_back_between_metadata_and_frames() {
+        let opts = slippi::de::Opts {
+            skip_frames: true,
+            ..Default::default()
+        };
+        let mut file = fs::File::open(fixture("game.slp")).unwrap();
+        let mut game = slippi::read(&mut file, Some(&opts)).unwrap();
+        assert!(game.frames.id.values().is_empty());
+
+        // Metadata alone is enough
+        let last_frame = game.metadata.as_ref().unwrap()["lastFrame"]
+            .as_i64()
+            .unwrap();
+        assert_eq!(
+            extract_game_duration(&game),
+            Some(playable_frames(last_frame as i32))
+        );
+
+        // Neither metadata nor frames
+        game.metadata = None;
+        assert_eq!(extract_game_duration(&game), None);
+    }
+
+    #[test]
+    fn tags_ignore_case_whitespace_and_full_width_hash() {
+        assert_eq!(normalize_tag(" bean#888\t"), "BEAN#888");
+        assert_eq!(normalize_tag("bean＃888"), "BEAN#888");
+        assert_eq!(normalize_tag("BEAN#888"), "BEAN#888");
+
+        assert!(same_tag("bean#888", "BEAN#888"));
+        assert!(same_tag("  BEAN#888 ", "bean#888"));
+        assert!(same_tag("bean＃888", "BEAN#888"));
+        assert!(!same_tag("BEAN#888", "BEAN#889"));
+        assert!(!same_tag("BEAN#888", "BEAN#8888"));
+        assert!(!same_tag("BEAN #888", "BEAN#888"));
+    }
+
+    #[test]
+    fn nametags_only_match_when_nobody_has_the_code() {
+        let replay = |players: Vec<PlayerInfo>| ReplayInfo {
+            players,
+            ..parse_replay(&fixture("game.slp")).unwrap()
+        };
+        let tagged = |name: &str, name_tag: Option<&str>| PlayerInfo {
+            name: name.to_string(),
+            name_tag: name_tag.map(str::to_string),
+            ..player(Port::P1, None)
+        };
+
+        // Offline: nobody has a connect code
+        let offline = replay(vec![
+            tagged("Unknown", Some("ALCE")),
+            tagged("Unknown", Some("bob")),
+        ]);
+        assert_eq!(
+            offline.player_index(PlayerTag::new("BOB#456", "BOB")),
+            Some(1)
+        );
+        assert_eq!(offline.player_index("BOB#456"), None);
+        assert_eq!(offline.player_index(PlayerTag::new("BOB#456", "")), None);
+
+        // The code wins over someone else wearing the same nametag
+        let online = replay(vec![
+            tagged("ALCE#123", Some("BOB")),
+            tagged("BOB#456", None),
+        ]);
+        assert_eq!(
+            online.player_index(PlayerTag::new("BOB#456", "BOB")),
+            Some(1)
+        );
+        assert_eq!(
+            online.player_index(PlayerTag::new("CARL#789", "BOB")),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn short_games_are_left_out_of_streaks_and_breakdowns() {
+        let game = |seconds: i32, result| ReplayInfo {
+            duration: Some(seconds * 60),
+            result,
+            ..parse_replay(&fixture("game.slp")).unwrap()
+        };
+        let mut analyzer = ReplayAnalyzer::new();
+        // Newest first: a win, an accidental start lost, a win, then a loss
+        analyzer.replays = vec![
+            game(120, GameResult::Player1Won),
+            game(5, GameResult::Player2Won),
+            game(120, GameResult::Player1Won),
+            game(120, GameResult::Player2Won),
+        ];
+        let me = "ALCE#123";
+        assert_eq!(analyzer.current_streak(me), 1);
+        assert_eq!(analyzer.longest_win_streak(me), 1);
+        assert_eq!(analyzer.win_streak_mask(me, 0..4), [false; 4]);
+
+        analyzer.min_game_frames = Some(30 * 60);
+        assert_eq!(analyzer.current_streak(me), 2);
+        assert_eq!(analyzer.longest_win_streak(me), 2);
+        assert_eq!(
+            analyzer.win_streak_mask(me, 0..4),
+            [true, false, true, false]
+        );
+        // Runs follow the order given, e.g. a sorted table
+        assert_eq!(analyzer.win_streak_mask(me, [0, 3, 2]), [false; 4]);
+        assert_eq!(
+            analyzer.win_streak_mask(me, [3, 2, 0]),
+            [true, false, true, false]
+        );
+        assert_eq!(analyzer.stats_since(me, SystemTime::UNIX_EPOCH), (3, 2, 1));
+        let stage = analyzer.replays[0].stage;
+        assert_eq!(analyzer.get_stats_by_stage(me)[&stage], (2, 1));
+    }
+
+    #[test]
+    fn expand_path_expands_home() {
+        let home = dirs::home_dir().unwrap().display().to_string();
+        assert_eq!(expand_path("~"), home);
+        assert_eq!(expand_path("~/Slippi"), format!("{home}/Slippi"));
+        assert_eq!(expand_path(" ~\\Slippi "), format!("{home}\\Slippi"));
+        // Someone else's home isn't looked up
+        assert_eq!(expand_path("~bob/Slippi"), "~bob/Slippi");
+    }
+
+    #[test]
+    fn expand_path_leaves_unknown_variables_as_written() {
+        for path in [
+            "$EPPI_TEST_UNSET/Slippi",
+            "${EPPI_TEST_UNSET}/Slippi",
+            "%EPPI_TEST_UNSET%\\Slippi",
+            "100% done",
+        ] {
+            assert_eq!(expand_path(path), path);
         }
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        assert_eq!(
+            expand_path("${CARGO_MANIFEST_DIR}/replays"),
+            format!("{manifest_dir}/replays")
+        );
+        assert_eq!(
+            expand_path("%CARGO_MANIFEST_DIR%/replays"),
+            format!("{manifest_dir}/replays")
+        );
     }
 
-    Ok(GameResult::Unknown)
+    #[test]
+    fn expand_path_keeps_absolute_paths() {
+        assert_eq!(expand_path("/home/me/Slippi"), "/home/me/Slippi");
+        assert_eq!(expand_path("  /home/me/Slippi\t"), "/home/me/Slippi");
+        assert_eq!(
+            expand_path("C:\\Users\\me\\Documents\\Slippi"),
+            "C:\\Users\\me\\Documents\\Slippi"
+        );
+    }
+
+    #[test]
+    fn scan_options_cover_paths_the_walk_would_visit() {
+        let dir = Path::new("/replays");
+        let options = ScanOptions {
+            max_depth: Some(2),
+            include_hidden: vec![".archive".to_string()],
+        };
+        assert!(options.covers(dir, Path::new("/replays/Game.slp")));
+        assert!(options.covers(dir, Path::new("/replays/2024-05/Game.slp")));
+        assert!(options.covers(dir, Path::new("/replays/.archive/Game.slp")));
+        // Too deep, hidden, or not under the directory at all
+        assert!(!options.covers(dir, Path::new("/replays/2024-05/old/Game.slp")));
+        assert!(!options.covers(dir, Path::new("/replays/.git/Game.slp")));
+        assert!(!options.covers(dir, Path::new("/other/Game.slp")));
+    }
 }
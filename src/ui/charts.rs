@@ -0,0 +1,165 @@
+//! Small charts: plots with axes via egui_plot, and bars drawn straight with the egui painter.
+
+use egui::{Align2, Color32, Pos2, Rect, Sense, Ui};
+use egui_plot::{Bar, BarChart, HLine, Line, Plot, PlotPoint, PlotPoints, Points, Text};
+
+/// Line chart of percentages over `x`, on a 0-100% y axis with a 50% guide line. The x axis is
+/// labeled at whole numbers with `format_x`, e.g. days as dates. `points` must be sorted by x.
+/// Returns the index of the point nearest the pointer while hovered, for the caller's readout.
+pub fn percent_line_chart(
+    ui: &mut Ui,
+    id_salt: &str,
+    points: &[(f64, f32)],
+    height: f32,
+    format_x: impl Fn(f64) -> String,
+) -> Option<usize> {
+    let line_color = ui.visuals().selection.bg_fill;
+    let highlight_color = ui.visuals().text_color();
+    let series: Vec<[f64; 2]> = points
+        .iter()
+        .map(|&(x, percent)| [x, f64::from(percent)])
+        .collect();
+
+    Plot::new(id_salt)
+        .height(height)
+        .include_y(0.0)
+        .include_y(100.0)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .allow_boxed_zoom(false)
+        .allow_double_click_reset(false)
+        // The caller shows the hovered point, so skip the plot's own crosshair readout
+        .show_x(false)
+        .show_y(false)
+        .x_axis_formatter(move |mark, _| {
+            if mark.value.fract() == 0.0 {
+                format_x(mark.value)
+            } else {
+                String::new()
+            }
+        })
+        .y_axis_formatter(|mark, _| format!("{}%", mark.value))
+        .show(ui, |plot_ui| {
+            plot_ui.hline(
+                HLine::new(50.0)
+                    .color(Color32::GRAY.gamma_multiply(0.5))
+                    .allow_hover(false),
+            );
+            plot_ui.line(
+                Line::new(PlotPoints::from(series.clone()))
+                    .color(line_color)
+                    .width(2.0)
+                    .allow_hover(false),
+            );
+            plot_ui.points(
+                Points::new(PlotPoints::from(series.clone()))
+                    .color(line_color)
+                    .radius(2.5)
+                    .allow_hover(false),
+            );
+
+            if !plot_ui.response().hovered() {
+                return None;
+            }
+            let pointer = plot_ui.pointer_coordinate()?;
+            let nearest = (0..series.len()).min_by(|&a, &b| {
+                let da = (series[a][0] - pointer.x).abs();
+                let db = (series[b][0] - pointer.x).abs();
+                da.total_cmp(&db)
+            })?;
+            plot_ui.points(
+                Points::new(series[nearest])
+                    .color(highlight_color)
+                    .radius(5.0)
+                    .filled(false)
+                    .allow_hover(false),
+            );
+            Some(nearest)
+        })
+        .inner
+}
+
+/// Horizontal bars of wins (green) over losses (red), with counts on the x axis and at the
+/// end of each bar.
+pub fn win_loss_chart(
+    ui: &mut Ui,
+    id_salt: &str,
+    wins: usize,
+    losses: usize,
+    size: egui::Vec2,
+) -> egui::Response {
+    // Wins on the upper row, at y = 1
+    let rows = [
+        ("Wins", wins, 1.0, Color32::from_rgb(60, 180, 75)),
+        ("Losses", losses, 0.0, Color32::from_rgb(200, 60, 60)),
+    ];
+    let bars = rows
+        .iter()
+        .map(|&(name, count, y, color)| Bar::new(y, count as f64).name(name).fill(color).width(0.7))
+        .collect();
+    let most = wins.max(losses).max(1) as f64;
+
+    Plot::new(id_salt)
+        .width(size.x)
+        .height(size.y)
+        .include_x(0.0)
+        // Room for the count after the longer bar
+        .include_x(most * 1.3)
+        .include_y(-0.5)
+        .include_y(1.5)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .allow_boxed_zoom(false)
+        .allow_double_click_reset(false)
+        .show_x(false)
+        .show_y(false)
+        .show_grid(false)
+        .x_axis_formatter(|mark, _| {
+            if mark.value.fract() == 0.0 && mark.value >= 0.0 {
+                format!("{}", mark.value)
+            } else {
+                String::new()
+            }
+        })
+        .y_axis_formatter(move |mark, _| {
+            rows.iter()
+                .find(|&&(_, _, y, _)| y == mark.value)
+                .map_or_else(String::new, |&(name, ..)| name.to_string())
+        })
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(BarChart::new(bars).horizontal());
+            for (_, count, y, _) in rows {
+                plot_ui.text(
+                    Text::new(PlotPoint::new(count as f64, y), format!(" {count}"))
+                        .anchor(Align2::LEFT_CENTER),
+                );
+            }
+        })
+        .response
+}
+
+/// A horizontal bar split into wins (green) and losses (red) by their share, without axes. For
+/// layouts too small for [`win_loss_chart`], like the stat card.
+pub fn win_loss_bar(ui: &mut Ui, wins: usize, losses: usize, size: egui::Vec2) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+    let painter = ui.painter_at(rect);
+    let total = wins + losses;
+    if total == 0 {
+        painter.rect_filled(rect, 2.0, Color32::GRAY.gamma_multiply(0.4));
+        return response;
+    }
+    let split = rect.left() + rect.width() * wins as f32 / total as f32;
+    painter.rect_filled(
+        Rect::from_min_max(rect.min, Pos2::new(split, rect.bottom())),
+        0.0,
+        Color32::from_rgb(60, 180, 75),
+    );
+    painter.rect_filled(
+        Rect::from_min_max(Pos2::new(split, rect.top()), rect.max),
+        0.0,
+        Color32::from_rgb(200, 60, 60),
+    );
+    response
+}
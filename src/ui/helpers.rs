@@ -1,4 +1,5 @@
-use egui::{self, Ui};
+use egui::{self, Color32, Ui};
+use peppi::game::Port;
 
 /// Number of manual rows used in the original table demo.
 pub const NUM_MANUAL_ROWS: usize = 20;
@@ -49,6 +50,31 @@ pub fn format_date(date: std::time::SystemTime) -> String {
     }
 }
 
+/// How much of the time [`format_timestamp`] shows.
+#[derive(Clone, Copy)]
+pub enum TimestampPrecision {
+    Minutes,
+    Seconds,
+}
+
+/// Format a SystemTime as an absolute date and time in the local time zone, e.g.
+/// "2025-03-14 21:05" or, to the second, "2025-03-14 21:05:32".
+pub fn format_timestamp(date: std::time::SystemTime, precision: TimestampPrecision) -> String {
+    let format = match precision {
+        TimestampPrecision::Minutes => "%Y-%m-%d %H:%M",
+        TimestampPrecision::Seconds => "%Y-%m-%d %H:%M:%S",
+    };
+    chrono::DateTime::<chrono::Local>::from(date)
+        .format(format)
+        .to_string()
+}
+
+/// Format a long span of frames (at 60 fps) as hours and minutes, e.g. "12h 05m".
+pub fn format_playtime(frames: u64) -> String {
+    let total_minutes = frames / 3_600;
+    format!("{}h {:02}m", total_minutes / 60, total_minutes % 60)
+}
+
 /// Format a number of frames (at 60 fps) into mm:ss.
 pub fn format_duration(frames: i32) -> String {
     let total_seconds = frames / 60; // Melee runs at 60 FPS
@@ -61,3 +87,13 @@ pub fn format_duration(frames: i32) -> String {
         format!("0:{seconds:02}")
     }
 }
+
+/// The color Melee uses for a controller port: red, blue, yellow and green for P1 to P4.
+pub fn port_color(port: Port) -> Color32 {
+    match port {
+        Port::P1 => Color32::from_rgb(241, 89, 89),
+        Port::P2 => Color32::from_rgb(101, 101, 254),
+        Port::P3 => Color32::from_rgb(254, 190, 63),
+        Port::P4 => Color32::from_rgb(76, 228, 76),
+    }
+}
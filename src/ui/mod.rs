@@ -1 +1,3 @@
+pub mod charts;
 pub mod helpers;
+pub mod stat_card;
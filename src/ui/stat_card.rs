@@ -0,0 +1,92 @@
+//! Shareable summary card, saved as a PNG by cropping a screenshot of it.
+
+use egui::{Color32, RichText, TextureHandle, Ui};
+
+use crate::ui::charts::win_loss_bar;
+
+/// Width of the card's contents, in points.
+const CARD_WIDTH: f32 = 260.0;
+
+/// The card paints its own colors so it looks the same in light and dark mode.
+const BACKGROUND: Color32 = Color32::from_rgb(27, 27, 34);
+const TEXT: Color32 = Color32::from_rgb(235, 235, 240);
+const MUTED: Color32 = Color32::from_rgb(150, 150, 160);
+
+/// What goes on a stat card.
+pub struct StatCard {
+    pub connect_code: String,
+    pub wins: usize,
+    pub losses: usize,
+    /// Positive for a win streak, negative for a losing one.
+    pub streak: i32,
+    /// Most played matchup and its record, e.g. "Fox vs Marth: 12-8 (60.0%)".
+    pub top_matchup: Option<String>,
+    /// Cached rank label, e.g. "Gold 2 (1602.3)".
+    pub rank: Option<String>,
+}
+
+/// Draw `card` in an opaque, square-cornered frame, so a screenshot cropped to the returned
+/// response's rect is all card.
+pub fn stat_card(
+    ui: &mut Ui,
+    card: &StatCard,
+    rank_icon: Option<&TextureHandle>,
+) -> egui::Response {
+    egui::Frame::new()
+        .fill(BACKGROUND)
+        .inner_margin(16.0)
+        .show(ui, |ui| {
+            ui.set_width(CARD_WIDTH);
+            ui.horizontal(|ui| {
+                if let Some(icon) = rank_icon {
+                    ui.add(
+                        egui::Image::from_texture(icon).fit_to_exact_size(egui::vec2(40.0, 40.0)),
+                    );
+                }
+                ui.vertical(|ui| {
+                    ui.label(RichText::new(&card.connect_code).heading().color(TEXT));
+                    if let Some(rank) = &card.rank {
+                        ui.label(RichText::new(rank).color(MUTED));
+                    }
+                });
+            });
+            ui.add_space(10.0);
+
+            let games = card.wins + card.losses;
+            let win_rate = if games > 0 {
+                card.wins as f64 / games as f64 * 100.0
+            } else {
+                0.0
+            };
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(format!("{}-{}", card.wins, card.losses))
+                        .size(28.0)
+                        .strong()
+                        .color(TEXT),
+                );
+                ui.label(RichText::new(format!("{win_rate:.1}% win rate")).color(MUTED));
+            });
+            win_loss_bar(ui, card.wins, card.losses, egui::vec2(CARD_WIDTH, 8.0));
+            ui.add_space(6.0);
+
+            let (streak_text, streak_color) = match card.streak {
+                s if s > 0 => (format!("W{s}"), Color32::GREEN),
+                s if s < 0 => (format!("L{}", -s), Color32::RED),
+                _ => ("-".to_string(), MUTED),
+            };
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Streak:").color(MUTED));
+                ui.label(RichText::new(streak_text).strong().color(streak_color));
+            });
+            if let Some(matchup) = &card.top_matchup {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Top matchup:").color(MUTED));
+                    ui.label(RichText::new(matchup).color(TEXT));
+                });
+            }
+            ui.add_space(6.0);
+            ui.label(RichText::new("eppi").small().color(MUTED));
+        })
+        .response
+}
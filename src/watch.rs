@@ -0,0 +1,205 @@
+use crate::peppi::{is_replay_file, parse_replay, ReplayInfo, ScanOptions};
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often replays that are still being written are checked on.
+const SETTLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a replay must go unchanged before it's treated as finished. Slippi writes replays
+/// incrementally during a game, so anything still growing is left alone.
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+/// Size and modified time, used to tell whether a file is still being written.
+type FileStamp = (u64, Option<SystemTime>);
+
+/// Watches a replay directory for file system events on a background thread and parses
+/// replays that appear or change after it started, once they stop changing. Nothing is
+/// polled while no game is being recorded. Stops when dropped.
+pub struct ReplayWatcher {
+    dir: PathBuf,
+    options: ScanOptions,
+    replays: mpsc::Receiver<ReplayInfo>,
+    walk_errors: mpsc::Receiver<(String, String)>,
+    /// Owns the event sender; dropping it ends the watcher thread.
+    _events: Option<notify::RecommendedWatcher>,
+}
+
+impl ReplayWatcher {
+    /// Start watching `dir`, walked per `options`. Replays already there are ignored. `on_new`
+    /// is called from the watcher thread after each new replay is sent, e.g. to request a
+    /// repaint. If `dir` can't be watched, that's reported through
+    /// [`Self::new_walk_errors`].
+    pub fn spawn(dir: PathBuf, options: ScanOptions, on_new: impl Fn() + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (error_tx, error_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+
+        let events = notify::recommended_watcher(event_tx).and_then(|mut events| {
+            events.watch(&dir, RecursiveMode::Recursive)?;
+            Ok(events)
+        });
+        let events = match events {
+            Ok(events) => {
+                let thread_dir = dir.clone();
+                let thread_options = options.clone();
+                thread::spawn(move || {
+                    watch(
+                        &thread_dir,
+                        &thread_options,
+                        event_rx,
+                        &tx,
+                        &error_tx,
+                        on_new,
+                    )
+                });
+                Some(events)
+            }
+            Err(e) => {
+                log::warn!("Couldn't watch {}: {e}", dir.display());
+                let _ = error_tx.send(notify_error(&dir, &e));
+                None
+            }
+        };
+
+        Self {
+            dir,
+            options,
+            replays: rx,
+            walk_errors: error_rx,
+            _events: events,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Replays finished since the last call.
+    pub fn new_replays(&self) -> impl Iterator<Item = ReplayInfo> + '_ {
+        self.replays.try_iter()
+    }
+
+    /// `(path, error)` for entries that couldn't be watched or read since the last call.
+    pub fn new_walk_errors(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        self.walk_errors.try_iter()
+    }
+}
+
+/// The watcher thread: collect replays that `events` says changed, and send each on `tx` once
+/// it has settled. Returns once the event sender or `tx`'s receiver is dropped.
+fn watch(
+    dir: &Path,
+    options: &ScanOptions,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    tx: &mpsc::Sender<ReplayInfo>,
+    error_tx: &mpsc::Sender<(String, String)>,
+    on_new: impl Fn(),
+) {
+    // Event paths are absolute and may have symlinks resolved, e.g. on macOS
+    let canonical_dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    // Files that are new or changed, with when they last changed
+    let mut pending: HashMap<PathBuf, (FileStamp, Instant)> = HashMap::new();
+    let mut last_check = Instant::now();
+
+    loop {
+        // Sleep until something happens; while a replay is pending, wake up to check on it
+        let received = if pending.is_empty() {
+            events
+                .recv()
+                .map_err(|_| mpsc::RecvTimeoutError::Disconnected)
+        } else {
+            events.recv_timeout(SETTLE_CHECK_INTERVAL)
+        };
+        match received {
+            Ok(Ok(event)) if event.kind.is_create() || event.kind.is_modify() => {
+                for path in event.paths {
+                    let covered =
+                        options.covers(dir, &path) || options.covers(&canonical_dir, &path);
+                    if !covered || !is_replay_file(&path) {
+                        continue;
+                    }
+                    if let Some(stamp) = file_stamp(&path) {
+                        pending.insert(path, (stamp, Instant::now()));
+                    }
+                }
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                log::warn!("Error watching {}: {e}", dir.display());
+                if error_tx.send(notify_error(dir, &e)).is_err() {
+                    return;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+
+        // A replay being recorded sends a steady stream of events, so check on the others
+        // at most once per interval rather than only when the events go quiet
+        if last_check.elapsed() < SETTLE_CHECK_INTERVAL {
+            continue;
+        }
+        last_check = Instant::now();
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, (_, changed))| changed.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            let Some((stamp, _)) = pending.remove(&path) else {
+                continue;
+            };
+            // Catch writes whose events were merged or missed
+            match file_stamp(&path) {
+                Some(current) if current == stamp => {}
+                Some(current) => {
+                    pending.insert(path, (current, Instant::now()));
+                    continue;
+                }
+                // Deleted before it settled
+                None => continue,
+            }
+
+            let Some(file_path) = path.to_str() else {
+                continue;
+            };
+            match parse_replay(file_path) {
+                Ok(replay) => {
+                    log::info!("New replay: {file_path}");
+                    if tx.send(replay).is_err() {
+                        return;
+                    }
+                    on_new();
+                }
+                Err(e) => log::warn!("Failed to parse new replay {file_path}: {e}"),
+            }
+        }
+    }
+}
+
+/// Size and modified time of the file at `path`, if it's still there.
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+/// `(path, error)` for a watch error, like [`crate::peppi::walk_error`] does for a scan.
+fn notify_error(dir: &Path, e: &notify::Error) -> (String, String) {
+    let path = e.paths.first().map_or(dir, PathBuf::as_path);
+    let error = match &e.kind {
+        notify::ErrorKind::Io(io_error) => io_error.to_string(),
+        notify::ErrorKind::Generic(message) => message.clone(),
+        kind => format!("{kind:?}"),
+    };
+    (path.display().to_string(), error)
+}
@@ -1,17 +1,289 @@
-/// Fetch a player's rank from the Slippi GraphQL API.
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+/// How many times [`fetch_player_rank`] tries before giving up.
+pub const RANK_FETCH_ATTEMPTS: u32 = 3;
+/// Delay before the first retry; doubled for each retry after that.
+pub const RANK_FETCH_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Slippi's GraphQL API, which rank lookups go to unless [`WebConfig`] overrides it.
+pub const DEFAULT_GRAPHQL_ENDPOINT: &str = "https://internal.slippi.gg/graphql";
+
+/// Default for [`WebConfig::requests_per_second`].
+pub const DEFAULT_REQUESTS_PER_SECOND: u32 = 3;
+
+/// Where rank lookups are sent, so they can go through a proxy or to a mock server.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct WebConfig {
+    /// GraphQL endpoint URL.
+    pub endpoint: String,
+    /// Sent as the `Authorization` header when not empty. slippi.gg itself needs none.
+    pub authorization: String,
+    /// Most requests sent per second across every lookup, so a batch lookup doesn't hammer
+    /// the community-run endpoint.
+    pub requests_per_second: u32,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: DEFAULT_GRAPHQL_ENDPOINT.to_string(),
+            authorization: String::new(),
+            requests_per_second: DEFAULT_REQUESTS_PER_SECOND,
+        }
+    }
+}
+
+/// Something that can look up a player's rank. The app uses [`SlippiRankProvider`]; a fake
+/// returning canned ranks lets the rank-update flow run without the network.
+pub trait RankProvider: Send + Sync {
+    fn fetch<'a>(&'a self, player_tag: &'a str)
+        -> BoxFuture<'a, Result<RankInfo, RankLookupError>>;
+}
+
+/// Looks ranks up on slippi.gg (or wherever `config` points) with [`fetch_player_rank`].
+#[derive(Debug, Clone, Default)]
+pub struct SlippiRankProvider {
+    pub config: WebConfig,
+}
+
+impl RankProvider for SlippiRankProvider {
+    fn fetch<'a>(
+        &'a self,
+        player_tag: &'a str,
+    ) -> BoxFuture<'a, Result<RankInfo, RankLookupError>> {
+        Box::pin(fetch_player_rank(&self.config, player_tag))
+    }
+}
+
+/// A player's rank on slippi.gg.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct RankInfo {
+    /// Rank name, e.g. "Diamond 1" or "Unranked".
+    pub rank: String,
+    /// Rating (`ratingOrdinal`), if the player has one this season.
+    pub rating: Option<f64>,
+    pub regional_placement: Option<i32>,
+    pub global_placement: Option<i32>,
+    /// Ranked set record this season; `None` when slippi.gg doesn't report one.
+    #[serde(default)]
+    pub wins: Option<u32>,
+    #[serde(default)]
+    pub losses: Option<u32>,
+}
+
+impl RankInfo {
+    /// A rank with no rating or placements attached.
+    pub fn unrated(rank: impl Into<String>) -> Self {
+        Self {
+            rank: rank.into(),
+            rating: None,
+            regional_placement: None,
+            global_placement: None,
+            wins: None,
+            losses: None,
+        }
+    }
+
+    /// The rank with its rating, e.g. "Diamond 1 (2031)".
+    pub fn label(&self) -> String {
+        match self.rating {
+            Some(rating) => format!("{} ({rating:.0})", self.rank),
+            None => self.rank.clone(),
+        }
+    }
+
+    /// The rank with this season's set record when there is one, e.g. "Gold 3 · 142-118".
+    pub fn summary(&self) -> String {
+        match (self.wins, self.losses) {
+            (Some(wins), Some(losses)) => format!("{} · {wins}-{losses}", self.rank),
+            _ => self.rank.clone(),
+        }
+    }
+}
+
+/// Just the rank name, as lookups returned before ratings were kept.
+impl fmt::Display for RankInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rank)
+    }
+}
+
+/// Why a rank lookup failed.
+#[derive(Debug, Clone)]
+pub enum RankLookupError {
+    /// The request didn't complete (DNS, TLS, timeout, ...).
+    Network(String),
+    /// The server answered with a non-success HTTP status.
+    Http(u16),
+    /// The response wasn't the JSON we expected.
+    InvalidResponse(String),
+    /// The GraphQL API reported errors.
+    Api(String),
+    /// No player or ranking data for this tag.
+    NotFound,
+}
+
+impl fmt::Display for RankLookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RankLookupError::Network(e) => write!(f, "network error: {e}"),
+            RankLookupError::Http(status) => write!(f, "server returned HTTP {status}"),
+            RankLookupError::InvalidResponse(e) => write!(f, "invalid response: {e}"),
+            RankLookupError::Api(e) => write!(f, "GraphQL API returned errors: {e}"),
+            RankLookupError::NotFound => write!(f, "Player not found or no ranking data available"),
+        }
+    }
+}
+
+impl std::error::Error for RankLookupError {}
+
+impl RankLookupError {
+    /// Whether trying again might help: network failures and server (5xx) errors. A well-formed
+    /// answer like "player not found" won't change on retry.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RankLookupError::Network(_) => true,
+            RankLookupError::Http(status) => (500..600).contains(status),
+            RankLookupError::InvalidResponse(_)
+            | RankLookupError::Api(_)
+            | RankLookupError::NotFound => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for RankLookupError {
+    fn from(e: reqwest::Error) -> Self {
+        RankLookupError::Network(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for RankLookupError {
+    fn from(e: serde_json::Error) -> Self {
+        RankLookupError::InvalidResponse(e.to_string())
+    }
+}
+
+/// Fetch ranks for many players, running up to `concurrency` lookups at once.
 ///
-/// This was previously defined in `peppi.rs`, but all HTTP / web
-/// functionality now lives inside `web.rs`.
+/// Duplicate tags are looked up once. `progress(done, total)` is called after each lookup
+/// finishes.
+pub async fn fetch_ranks_batch(
+    provider: &dyn RankProvider,
+    tags: Vec<String>,
+    concurrency: usize,
+    progress: impl Fn(usize, usize),
+) -> HashMap<String, Result<RankInfo, RankLookupError>> {
+    let mut tags = tags;
+    tags.sort_unstable();
+    tags.dedup();
+    let total = tags.len();
+
+    let mut lookups = stream::iter(tags)
+        .map(|tag| async move {
+            let result = provider.fetch(&tag).await;
+            (tag, result)
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    let mut results = HashMap::with_capacity(total);
+    while let Some((tag, result)) = lookups.next().await {
+        results.insert(tag, result);
+        progress(results.len(), total);
+    }
+    results
+}
+
+/// Fetch a player's rank from the Slippi GraphQL API.
 ///
-/// Returns the rank as a `String` on success or an error on failure.
+/// Returns the rank and rating on success or an error on failure. Transient failures are
+/// retried; see [`fetch_player_rank_with_retries`].
 pub async fn fetch_player_rank(
+    config: &WebConfig,
     player_tag: &str,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    log::info!("🌐 Fetching rank for player: {player_tag} via Slippi GraphQL API");
+) -> Result<RankInfo, RankLookupError> {
+    fetch_player_rank_with_retries(
+        config,
+        player_tag,
+        RANK_FETCH_ATTEMPTS,
+        RANK_FETCH_BASE_DELAY,
+    )
+    .await
+}
 
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36")
-        .build()?;
+/// [`fetch_player_rank`] with explicit retry settings: up to `attempts` tries, waiting
+/// `base_delay` before the first retry and doubling the wait each time after. Only
+/// [transient](RankLookupError::is_transient) errors are retried.
+pub async fn fetch_player_rank_with_retries(
+    config: &WebConfig,
+    player_tag: &str,
+    attempts: u32,
+    base_delay: Duration,
+) -> Result<RankInfo, RankLookupError> {
+    let client = http_client()?;
+    let mut delay = base_delay;
+    let mut attempt = 1;
+    loop {
+        match fetch_player_rank_once(client, config, player_tag).await {
+            Err(e) if e.is_transient() && attempt < attempts => {
+                log::warn!(
+                    "Rank lookup for {player_tag} failed ({e}), retrying in {delay:?} \
+                     (attempt {attempt}/{attempts})"
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Wait for the next free request slot. Every request (retries included) goes through here,
+/// so requests leave at most `per_second` a second however many lookups run at once.
+async fn wait_for_request_slot(per_second: u32) {
+    static NEXT_SLOT: Mutex<Option<tokio::time::Instant>> = Mutex::new(None);
+    let interval = Duration::from_secs(1) / per_second.max(1);
+    let slot = {
+        let mut next = NEXT_SLOT.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = tokio::time::Instant::now();
+        let slot = next.map_or(now, |next| next.max(now));
+        *next = Some(slot + interval);
+        slot
+    };
+    tokio::time::sleep_until(slot).await;
+}
+
+/// HTTP client shared by every lookup so connections (and TLS sessions) are reused. Building
+/// it only fails when TLS can't be set up, which every lookup then reports.
+fn http_client() -> Result<&'static reqwest::Client, RankLookupError> {
+    static CLIENT: OnceLock<Result<reqwest::Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::Client::builder()
+                .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36")
+                .build()
+                .map_err(|e| {
+                    log::error!("Failed to build HTTP client: {e}");
+                    e.to_string()
+                })
+        })
+        .as_ref()
+        .map_err(|e| RankLookupError::Network(format!("couldn't create HTTP client: {e}")))
+}
+
+async fn fetch_player_rank_once(
+    client: &reqwest::Client,
+    config: &WebConfig,
+    player_tag: &str,
+) -> Result<RankInfo, RankLookupError> {
+    log::info!("🌐 Fetching rank for player: {player_tag} via Slippi GraphQL API");
 
     // GraphQL query to get user profile by connect code
     let query = r#"
@@ -23,6 +295,8 @@ pub async fn fetch_player_rank(
           }
           rankedNetplayProfile {
             ratingOrdinal
+            wins
+            losses
             dailyGlobalPlacement
             dailyRegionalPlacement
           }
@@ -38,14 +312,19 @@ pub async fn fetch_player_rank(
         }
     });
 
-    let response = client
-        .post("https://internal.slippi.gg/graphql")
-        .header("content-type", "application/json")
-        .json(&json_data)
-        .send()
-        .await?;
+    wait_for_request_slot(config.requests_per_second).await;
+    let mut request = client
+        .post(&config.endpoint)
+        .header("content-type", "application/json");
+    if !config.authorization.is_empty() {
+        request = request.header("authorization", &config.authorization);
+    }
+    let response = request.json(&json_data).send().await?;
 
     log::debug!("📡 GraphQL Status: {}", response.status());
+    if response.status().is_server_error() {
+        return Err(RankLookupError::Http(response.status().as_u16()));
+    }
 
     let response_text = response.text().await?;
     log::debug!("📄 Response length: {} characters", response_text.len());
@@ -65,20 +344,39 @@ pub async fn fetch_player_rank(
                 let regional_placement = ranked_profile
                     .get("dailyRegionalPlacement")
                     .and_then(|p| p.as_i64())
-                    .unwrap_or(i64::MAX) as i32;
+                    .map(|p| p as i32);
                 let global_placement = ranked_profile
                     .get("dailyGlobalPlacement")
                     .and_then(|p| p.as_i64())
-                    .unwrap_or(i64::MAX) as i32;
+                    .map(|p| p as i32);
 
-                let rank = elo_to_rank(rating_ordinal as i32, regional_placement, global_placement);
-                log::info!("✅ Found rank: {rank} (ELO: {rating_ordinal}, Regional: {regional_placement}, Global: {global_placement})");
-                return Ok(rank);
+                let rank = elo_to_rank(
+                    rating_ordinal as i32,
+                    regional_placement.unwrap_or(i32::MAX),
+                    global_placement.unwrap_or(i32::MAX),
+                );
+                let record = |field: &str| {
+                    ranked_profile
+                        .get(field)
+                        .and_then(|n| n.as_u64())
+                        .map(|n| n as u32)
+                };
+                log::info!("✅ Found rank: {rank} (ELO: {rating_ordinal}, Regional: {regional_placement:?}, Global: {global_placement:?})");
+                return Ok(RankInfo {
+                    rank,
+                    rating: Some(rating_ordinal),
+                    regional_placement,
+                    global_placement,
+                    wins: record("wins"),
+                    losses: record("losses"),
+                });
             } else {
                 // Player has a ranked profile but no ratingOrdinal (e.g., unranked season)
                 log::warn!("⚠️  Player has ranked profile but no ratingOrdinal.");
                 if let Some(display_name) = user_data.get("displayName").and_then(|n| n.as_str()) {
-                    return Ok(format!("{display_name} (Unranked Season)"));
+                    return Ok(RankInfo::unrated(format!(
+                        "{display_name} (Unranked Season)"
+                    )));
                 }
             }
         }
@@ -88,44 +386,450 @@ pub async fn fetch_player_rank(
             log::warn!(
                 "⚠️  Player '{display_name}' found but has no ranked netplay profile (or no ratingOrdinal)."
             );
-            return Ok("Unranked".to_string());
+            return Ok(RankInfo::unrated("Unranked"));
         }
     }
 
     // Check for errors in the response (e.g., player not found)
     if let Some(errors) = json_response.get("errors") {
         log::error!("❌ GraphQL errors: {errors}");
-        return Err(format!("GraphQL API returned errors: {errors}").into());
+        return Err(RankLookupError::Api(errors.to_string()));
     }
 
     log::error!("❌ Player not found or no ranking data available in response: {json_response}");
-    Err("Player not found or no ranking data available".into())
+    Err(RankLookupError::NotFound)
+}
+
+/// Lowest rating for each tier below Grandmaster, in ascending order.
+const RANK_THRESHOLDS: [(i32, &str); 18] = [
+    (i32::MIN, "Bronze 1"),
+    (766, "Bronze 2"),
+    (914, "Bronze 3"),
+    (1055, "Silver 1"),
+    (1189, "Silver 2"),
+    (1316, "Silver 3"),
+    (1436, "Gold 1"),
+    (1549, "Gold 2"),
+    (1654, "Gold 3"),
+    (1752, "Platinum 1"),
+    (1843, "Platinum 2"),
+    (1928, "Platinum 3"),
+    (2004, "Diamond 1"),
+    (2074, "Diamond 2"),
+    (2137, "Diamond 3"),
+    (2192, "Master 1"),
+    (2275, "Master 2"),
+    (2350, "Master 3"),
+];
+
+/// Every rank tier from lowest to highest, as named by [`elo_to_rank`].
+pub const RANK_TIERS: [&str; RANK_THRESHOLDS.len() + 1] = {
+    let mut tiers = ["Grandmaster"; RANK_THRESHOLDS.len() + 1];
+    let mut i = 0;
+    while i < RANK_THRESHOLDS.len() {
+        tiers[i] = RANK_THRESHOLDS[i].1;
+        i += 1;
+    }
+    tiers
+};
+
+/// Position of `rank` in [`RANK_TIERS`], so ranks sort Bronze to Grandmaster instead of
+/// alphabetically. `None` for "Unranked", "Unknown" and anything else that isn't a tier.
+pub fn rank_ordinal(rank: &str) -> Option<usize> {
+    RANK_TIERS.iter().position(|&tier| tier == rank)
+}
+
+/// Grandmaster takes a Master rating plus a top 100 regional or top 300 global placement.
+const GRANDMASTER_MIN_RATING: i32 = 2192;
+const GRANDMASTER_MAX_REGIONAL_PLACEMENT: i32 = 100;
+const GRANDMASTER_MAX_GLOBAL_PLACEMENT: i32 = 300;
+
+/// Rating cutoffs [`elo_to_rank`] uses: the built-in ones, patched by `ranks.toml`.
+#[derive(Debug, Clone, PartialEq)]
+struct RankThresholds {
+    tiers: [(i32, &'static str); 18],
+    grandmaster_min_rating: i32,
+}
+
+impl Default for RankThresholds {
+    fn default() -> Self {
+        Self {
+            tiers: RANK_THRESHOLDS,
+            grandmaster_min_rating: GRANDMASTER_MIN_RATING,
+        }
+    }
+}
+
+impl RankThresholds {
+    /// Built-in thresholds with the ones in `text` swapped in. Expects a `[thresholds]` table
+    /// of tier names to lowest rating, e.g. `"Gold 1" = 1440`; tiers left out keep their
+    /// built-in cutoff. Every tier must still need more rating than the one before it, with
+    /// Grandmaster needing at least as much as Master 1.
+    fn parse(text: &str) -> Result<Self, String> {
+        let document: toml_edit::DocumentMut = text.parse().map_err(|e| format!("{e}"))?;
+        let mut thresholds = Self::default();
+        let Some(table) = document.get("thresholds") else {
+            return Ok(thresholds);
+        };
+        let table = table
+            .as_table_like()
+            .ok_or("`thresholds` must be a table")?;
+        for (name, value) in table.iter() {
+            let rating = value
+                .as_integer()
+                .and_then(|rating| i32::try_from(rating).ok())
+                .ok_or_else(|| format!("Threshold for {name} must be a whole number"))?;
+            if name == "Grandmaster" {
+                thresholds.grandmaster_min_rating = rating;
+                continue;
+            }
+            match thresholds
+                .tiers
+                .iter_mut()
+                .position(|(_, tier)| *tier == name)
+            {
+                Some(0) => return Err(format!("{name} is the lowest tier and has no threshold")),
+                Some(index) => thresholds.tiers[index].0 = rating,
+                None => return Err(format!("Unknown rank tier: {name}")),
+            }
+        }
+        if let Some(pair) = thresholds
+            .tiers
+            .windows(2)
+            .find(|pair| pair[1].0 <= pair[0].0)
+        {
+            return Err(format!(
+                "{} ({}) must need more rating than {} ({})",
+                pair[1].1, pair[1].0, pair[0].1, pair[0].0
+            ));
+        }
+        if let Some((master_min_rating, master)) =
+            thresholds.tiers.iter().find(|(min_rating, tier)| {
+                *tier == "Master 1" && thresholds.grandmaster_min_rating < *min_rating
+            })
+        {
+            return Err(format!(
+                "Grandmaster ({}) must need at least as much rating as {master} ({master_min_rating})",
+                thresholds.grandmaster_min_rating
+            ));
+        }
+        Ok(thresholds)
+    }
+
+    /// The tier `rating` falls in under these cutoffs.
+    fn rank(&self, rating: i32, regional_placement: i32, global_placement: i32) -> &'static str {
+        if rating >= self.grandmaster_min_rating
+            && (regional_placement <= GRANDMASTER_MAX_REGIONAL_PLACEMENT
+                || global_placement <= GRANDMASTER_MAX_GLOBAL_PLACEMENT)
+        {
+            return "Grandmaster";
+        }
+        self.tiers
+            .iter()
+            .rev()
+            .find(|(min_rating, _)| rating >= *min_rating)
+            .map_or("Unranked", |(_, name)| name)
+    }
+}
+
+/// Where users can override rank cutoffs when Slippi re-tunes them between seasons.
+pub fn rank_thresholds_path() -> PathBuf {
+    crate::peppi::cache_dir().join("ranks.toml")
+}
+
+/// Thresholds from [`rank_thresholds_path`], read once. A missing file uses the built-ins; so
+/// does an invalid one, with a warning.
+fn rank_thresholds() -> &'static RankThresholds {
+    static THRESHOLDS: OnceLock<RankThresholds> = OnceLock::new();
+    THRESHOLDS.get_or_init(|| {
+        let path = rank_thresholds_path();
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return RankThresholds::default();
+        };
+        RankThresholds::parse(&text).unwrap_or_else(|e| {
+            log::warn!("Ignoring {}: {e}", path.display());
+            RankThresholds::default()
+        })
+    })
 }
 
 /// Convert an ELO value into the human-readable rank string used by Slippi.
 fn elo_to_rank(rating: i32, regional_placement: i32, global_placement: i32) -> String {
-    match rating {
-        r if r < 766 => "Bronze 1".to_string(),
-        r if r < 914 => "Bronze 2".to_string(),
-        r if r < 1055 => "Bronze 3".to_string(),
-        r if r < 1189 => "Silver 1".to_string(),
-        r if r < 1316 => "Silver 2".to_string(),
-        r if r < 1436 => "Silver 3".to_string(),
-        r if r < 1549 => "Gold 1".to_string(),
-        r if r < 1654 => "Gold 2".to_string(),
-        r if r < 1752 => "Gold 3".to_string(),
-        r if r < 1843 => "Platinum 1".to_string(),
-        r if r < 1928 => "Platinum 2".to_string(),
-        r if r < 2004 => "Platinum 3".to_string(),
-        r if r < 2074 => "Diamond 1".to_string(),
-        r if r < 2137 => "Diamond 2".to_string(),
-        r if r < 2192 => "Diamond 3".to_string(),
-        r if r >= 2192 && (regional_placement <= 100 || global_placement <= 300) => {
-            "Grandmaster".to_string()
-        }
-        r if r < 2275 => "Master 1".to_string(),
-        r if r < 2350 => "Master 2".to_string(),
-        r if r >= 2350 => "Master 3".to_string(),
-        _ => "Unranked".to_string(),
+    rank_thresholds()
+        .rank(rating, regional_placement, global_placement)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Placement too low for Grandmaster.
+    const UNPLACED: i32 = i32::MAX;
+
+    #[test]
+    fn rank_tiers_run_from_bronze_to_grandmaster() {
+        assert_eq!(
+            RANK_TIERS,
+            [
+                "Bronze 1",
+                "Bronze 2",
+                "Bronze 3",
+                "Silver 1",
+                "Silver 2",
+                "Silver 3",
+                "Gold 1",
+                "Gold 2",
+                "Gold 3",
+                "Platinum 1",
+                "Platinum 2",
+                "Platinum 3",
+                "Diamond 1",
+                "Diamond 2",
+                "Diamond 3",
+                "Master 1",
+                "Master 2",
+                "Master 3",
+                "Grandmaster",
+            ]
+        );
+    }
+
+    #[test]
+    fn ratings_on_each_side_of_a_cutoff() {
+        let thresholds = RankThresholds::default();
+        // The last rating of each tier, then the first of the next
+        for (rating, tier) in [
+            (i32::MIN, "Bronze 1"),
+            (0, "Bronze 1"),
+            (765, "Bronze 1"),
+            (766, "Bronze 2"),
+            (913, "Bronze 2"),
+            (914, "Bronze 3"),
+            (1054, "Bronze 3"),
+            (1055, "Silver 1"),
+            (1188, "Silver 1"),
+            (1189, "Silver 2"),
+            (1315, "Silver 2"),
+            (1316, "Silver 3"),
+            (1435, "Silver 3"),
+            (1436, "Gold 1"),
+            (1548, "Gold 1"),
+            (1549, "Gold 2"),
+            (1653, "Gold 2"),
+            (1654, "Gold 3"),
+            (1751, "Gold 3"),
+            (1752, "Platinum 1"),
+            (1842, "Platinum 1"),
+            (1843, "Platinum 2"),
+            (1927, "Platinum 2"),
+            (1928, "Platinum 3"),
+            (2003, "Platinum 3"),
+            (2004, "Diamond 1"),
+            (2073, "Diamond 1"),
+            (2074, "Diamond 2"),
+            (2136, "Diamond 2"),
+            (2137, "Diamond 3"),
+            (2191, "Diamond 3"),
+            (2192, "Master 1"),
+            (2274, "Master 1"),
+            (2275, "Master 2"),
+            (2349, "Master 2"),
+            (2350, "Master 3"),
+            (3000, "Master 3"),
+        ] {
+            assert_eq!(
+                thresholds.rank(rating, UNPLACED, UNPLACED),
+                tier,
+                "rating {rating}"
+            );
+        }
+    }
+
+    #[test]
+    fn grandmaster_needs_rating_and_placement() {
+        let thresholds = RankThresholds::default();
+        let cutoff = GRANDMASTER_MIN_RATING;
+        assert_eq!(
+            thresholds.rank(cutoff, GRANDMASTER_MAX_REGIONAL_PLACEMENT, UNPLACED),
+            "Grandmaster"
+        );
+        assert_eq!(
+            thresholds.rank(cutoff, UNPLACED, GRANDMASTER_MAX_GLOBAL_PLACEMENT),
+            "Grandmaster"
+        );
+        assert_eq!(
+            thresholds.rank(
+                cutoff,
+                GRANDMASTER_MAX_REGIONAL_PLACEMENT + 1,
+                GRANDMASTER_MAX_GLOBAL_PLACEMENT + 1
+            ),
+            "Master 1"
+        );
+        assert_eq!(thresholds.rank(cutoff - 1, 1, 1), "Diamond 3");
+    }
+
+    #[test]
+    fn thresholds_file_patches_the_built_ins() {
+        let thresholds =
+            RankThresholds::parse("[thresholds]\n\"Gold 1\" = 1440\nGrandmaster = 2400\n").unwrap();
+        assert_eq!(thresholds.rank(1440, UNPLACED, UNPLACED), "Gold 1");
+        assert_eq!(thresholds.rank(1439, UNPLACED, UNPLACED), "Silver 3");
+        assert_eq!(thresholds.rank(2399, 1, 1), "Master 3");
+        assert_eq!(thresholds.rank(2400, 1, 1), "Grandmaster");
+        // Everything else keeps its built-in cutoff
+        assert_eq!(thresholds.rank(1549, UNPLACED, UNPLACED), "Gold 2");
+        assert_eq!(
+            RankThresholds::parse("").unwrap(),
+            RankThresholds::default()
+        );
+    }
+
+    #[test]
+    fn thresholds_must_stay_in_order() {
+        let error = RankThresholds::parse("[thresholds]\n\"Gold 2\" = 1400\n").unwrap_err();
+        assert!(error.contains("Gold 2"), "{error}");
+        let error = RankThresholds::parse("[thresholds]\nGrandmaster = 2000\n").unwrap_err();
+        assert!(error.contains("Grandmaster"), "{error}");
+        // Grandmaster at exactly the Master 1 cutoff is how the built-ins have it
+        assert!(RankThresholds::parse("[thresholds]\nGrandmaster = 2192\n").is_ok());
+    }
+
+    #[test]
+    fn thresholds_reject_unknown_and_lowest_tiers() {
+        let error = RankThresholds::parse("[thresholds]\n\"Gold 4\" = 1700\n").unwrap_err();
+        assert_eq!(error, "Unknown rank tier: Gold 4");
+        let error = RankThresholds::parse("[thresholds]\n\"Bronze 1\" = 0\n").unwrap_err();
+        assert_eq!(error, "Bronze 1 is the lowest tier and has no threshold");
+    }
+
+    /// Requests a [`mock_graphql`] server has seen, by connect code.
+    type RequestCounts = Arc<Mutex<HashMap<String, usize>>>;
+
+    /// Serve canned GraphQL answers on a local port: `GOOD#1` has a rating, `GONE#4` doesn't
+    /// exist, `DOWN#3` always gets a 503 and `FLAKY#2` gets one only on its first request.
+    async fn mock_graphql() -> (WebConfig, RequestCounts) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = WebConfig {
+            endpoint: format!("http://{}/graphql", listener.local_addr().unwrap()),
+            authorization: String::new(),
+            requests_per_second: 1_000,
+        };
+        let requests = RequestCounts::default();
+        let counts = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(answer(stream, counts.clone()));
+            }
+        });
+        (config, requests)
+    }
+
+    async fn answer(mut stream: tokio::net::TcpStream, requests: RequestCounts) {
+        let mut request = Vec::new();
+        let mut chunk = [0; 4096];
+        let body: serde_json::Value = loop {
+            let read = stream.read(&mut chunk).await.unwrap();
+            if read == 0 {
+                return;
+            }
+            request.extend_from_slice(&chunk[..read]);
+            let text = String::from_utf8_lossy(&request);
+            let Some(header_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let length = text[..header_end]
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            let body_start = header_end + 4;
+            if request.len() >= body_start + length {
+                break serde_json::from_slice(&request[body_start..body_start + length]).unwrap();
+            }
+        };
+
+        let tag = body["variables"]["cc"].as_str().unwrap().to_string();
+        let attempt = {
+            let mut requests = requests.lock().unwrap();
+            let count = requests.entry(tag.clone()).or_default();
+            *count += 1;
+            *count
+        };
+        let found = r#"{"data":{"getUser":{"displayName":"Good","rankedNetplayProfile":
+            {"ratingOrdinal":1600.5,"wins":10,"losses":5}}}}"#;
+        let (status, body) = match (tag.as_str(), attempt) {
+            ("GOOD#1", _) | ("FLAKY#2", 2..) => ("200 OK", found),
+            ("DOWN#3", _) | ("FLAKY#2", _) => ("503 Service Unavailable", "{}"),
+            _ => ("200 OK", r#"{"data":{"getUser":null}}"#),
+        };
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    fn request_count(requests: &RequestCounts, tag: &str) -> usize {
+        requests.lock().unwrap().get(tag).copied().unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn batch_lookups_dedupe_tags_and_keep_partial_failures() {
+        let (config, requests) = mock_graphql().await;
+        let provider = SlippiRankProvider { config };
+        let tags = ["GOOD#1", "GONE#4", "GOOD#1", "DOWN#3"].map(String::from);
+        let progress = Mutex::new(Vec::new());
+
+        let results = fetch_ranks_batch(&provider, tags.to_vec(), 2, |done, total| {
+            progress.lock().unwrap().push((done, total));
+        })
+        .await;
+
+        assert_eq!(results.len(), 3);
+        let good = results["GOOD#1"].as_ref().unwrap();
+        assert_eq!(good.rating, Some(1600.5));
+        assert_eq!((good.wins, good.losses), (Some(10), Some(5)));
+        assert!(matches!(results["GONE#4"], Err(RankLookupError::NotFound)));
+        assert!(matches!(results["DOWN#3"], Err(RankLookupError::Http(503))));
+        assert_eq!(progress.into_inner().unwrap(), [(1, 3), (2, 3), (3, 3)]);
+
+        assert_eq!(request_count(&requests, "GOOD#1"), 1);
+        assert_eq!(request_count(&requests, "GONE#4"), 1);
+        assert_eq!(
+            request_count(&requests, "DOWN#3"),
+            RANK_FETCH_ATTEMPTS as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn transient_errors_are_retried_with_backoff() {
+        let (config, requests) = mock_graphql().await;
+        let base_delay = Duration::from_millis(50);
+
+        let rank = fetch_player_rank_with_retries(&config, "FLAKY#2", 2, base_delay).await;
+        assert_eq!(rank.unwrap().rating, Some(1600.5));
+        assert_eq!(request_count(&requests, "FLAKY#2"), 2);
+
+        // Waits 50ms, then 100ms, before giving up after the third try
+        let started = Instant::now();
+        let rank = fetch_player_rank_with_retries(&config, "DOWN#3", 3, base_delay).await;
+        assert!(matches!(rank, Err(RankLookupError::Http(503))));
+        assert_eq!(request_count(&requests, "DOWN#3"), 3);
+        assert!(started.elapsed() >= base_delay * 3);
+
+        // A single attempt doesn't retry, and a missing player isn't worth retrying
+        let rank = fetch_player_rank_with_retries(&config, "DOWN#3", 1, base_delay).await;
+        assert!(rank.is_err());
+        assert_eq!(request_count(&requests, "DOWN#3"), 4);
+        let rank = fetch_player_rank_with_retries(&config, "GONE#4", 3, base_delay).await;
+        assert!(matches!(rank, Err(RankLookupError::NotFound)));
+        assert_eq!(request_count(&requests, "GONE#4"), 1);
     }
 }